@@ -0,0 +1,416 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    app::{AnnotationKind, FrameAnnotation},
+    project::Label,
+};
+
+/// Frame-level agreement of two annotators for a single label.
+#[derive(Debug, Clone)]
+pub struct LabelAgreement {
+    pub label: Label,
+    pub kappa: f64,
+    pub frames_a: u64,
+    pub frames_b: u64,
+    pub frames_both: u64,
+}
+
+/// A pair of segments with the same label, or a segment without a partner.
+#[derive(Debug, Clone)]
+pub struct SegmentMatch {
+    pub label: Label,
+    pub a: Option<(u32, u32)>,
+    pub b: Option<(u32, u32)>,
+    pub iou: f64,
+}
+
+/// A run of frames where the two annotators assigned different label sets.
+#[derive(Debug, Clone)]
+pub struct Disagreement {
+    pub start_frame: u32,
+    pub end_frame: u32,
+    pub labels_a: Vec<Label>,
+    pub labels_b: Vec<Label>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AgreementReport {
+    pub total_frames: u64,
+    pub labels: Vec<LabelAgreement>,
+    pub matches: Vec<SegmentMatch>,
+    pub disagreements: Vec<Disagreement>,
+}
+
+pub fn compare(
+    a: &[FrameAnnotation],
+    b: &[FrameAnnotation],
+    total_frames: u64,
+    iou_threshold: f64,
+) -> AgreementReport {
+    let labels = a
+        .iter()
+        .chain(b.iter())
+        .map(|annotation| annotation.label.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let runs = runs(a, b, &labels, total_frames);
+
+    AgreementReport {
+        total_frames,
+        labels: labels
+            .iter()
+            .enumerate()
+            .map(|(index, label)| label_agreement(&runs, index, label, total_frames))
+            .collect(),
+        matches: labels
+            .iter()
+            .flat_map(|label| match_segments(a, b, label, iou_threshold))
+            .collect(),
+        disagreements: disagreements(&runs, &labels),
+    }
+}
+
+/// Frames `start..=end` over which the labels of both annotators stay the same, as
+/// indexes into the sorted labels.
+struct Run {
+    start: u64,
+    end: u64,
+    labels_a: Vec<usize>,
+    labels_b: Vec<usize>,
+}
+
+/// Inclusive frames an annotation covers, events with their tolerance window.
+fn span(annotation: &FrameAnnotation) -> (u64, u64) {
+    match annotation.kind {
+        AnnotationKind::Interval => (annotation.start_frame as u64, annotation.end_frame as u64),
+        AnnotationKind::Event { tolerance } => (
+            annotation.start_frame.saturating_sub(tolerance) as u64,
+            annotation.start_frame as u64 + tolerance as u64,
+        ),
+    }
+}
+
+/// Splits `0..total_frames` into runs of equal label sets by sweeping over the segment
+/// boundaries, so that the work depends on the number of annotations and not on the
+/// number of frames.
+fn runs(
+    a: &[FrameAnnotation],
+    b: &[FrameAnnotation],
+    labels: &[Label],
+    total_frames: u64,
+) -> Vec<Run> {
+    // (frame, side, label, change of the number of segments covering the frame),
+    // side 0 is annotator a and 1 annotator b
+    let mut boundaries = Vec::new();
+    for (side, annotations) in [a, b].into_iter().enumerate() {
+        for annotation in annotations {
+            let label = labels.binary_search(&annotation.label).unwrap();
+            let (start, end) = span(annotation);
+            boundaries.push((start.min(total_frames), side, label, 1));
+            boundaries.push(((end + 1).min(total_frames), side, label, -1));
+        }
+    }
+    boundaries.push((total_frames, 0, 0, 0));
+    boundaries.sort_unstable();
+
+    let mut covering = [vec![0i32; labels.len()], vec![0i32; labels.len()]];
+    let mut runs: Vec<Run> = Vec::new();
+    let mut previous = 0;
+    for (frame, side, label, change) in boundaries {
+        if frame > previous {
+            let set = |covering: &[i32]| {
+                (0..labels.len())
+                    .filter(|label| covering[*label] > 0)
+                    .collect::<Vec<_>>()
+            };
+            let (labels_a, labels_b) = (set(&covering[0]), set(&covering[1]));
+            match runs.last_mut() {
+                Some(last) if last.labels_a == labels_a && last.labels_b == labels_b => {
+                    last.end = frame - 1;
+                }
+                _ => runs.push(Run {
+                    start: previous,
+                    end: frame - 1,
+                    labels_a,
+                    labels_b,
+                }),
+            }
+            previous = frame;
+        }
+        if frame == total_frames {
+            break;
+        }
+        covering[side][label] += change;
+    }
+    runs
+}
+
+/// Cohen's kappa over the binary "frame has label" decision of both annotators.
+fn label_agreement(runs: &[Run], index: usize, label: &str, total_frames: u64) -> LabelAgreement {
+    let (mut frames_a, mut frames_b, mut frames_both) = (0, 0, 0);
+    for run in runs {
+        let frames = run.end - run.start + 1;
+        match (run.labels_a.contains(&index), run.labels_b.contains(&index)) {
+            (true, true) => frames_both += frames,
+            (true, false) => frames_a += frames,
+            (false, true) => frames_b += frames,
+            (false, false) => {}
+        }
+    }
+    let frames_neither = total_frames - frames_a - frames_b - frames_both;
+
+    let n = total_frames.max(1) as f64;
+    let observed = (frames_both + frames_neither) as f64 / n;
+    let p_a = (frames_both + frames_a) as f64 / n;
+    let p_b = (frames_both + frames_b) as f64 / n;
+    let expected = p_a * p_b + (1.0 - p_a) * (1.0 - p_b);
+    let kappa = if expected >= 1.0 {
+        1.0
+    } else {
+        (observed - expected) / (1.0 - expected)
+    };
+
+    LabelAgreement {
+        label: label.to_string(),
+        kappa,
+        frames_a: frames_a + frames_both,
+        frames_b: frames_b + frames_both,
+        frames_both,
+    }
+}
+
+pub fn segment_iou(a: (u32, u32), b: (u32, u32)) -> f64 {
+    let intersection_start = a.0.max(b.0);
+    let intersection_end = a.1.min(b.1);
+    if intersection_start > intersection_end {
+        return 0.0;
+    }
+    let intersection = (intersection_end - intersection_start) as f64 + 1.0;
+    let union = (a.1 - a.0) as f64 + (b.1 - b.0) as f64 + 2.0 - intersection;
+    intersection / union
+}
+
+fn segments(annotations: &[FrameAnnotation], label: &str) -> Vec<(u32, u32)> {
+    annotations
+        .iter()
+        .filter(|annotation| {
+            annotation.label == label && annotation.kind == AnnotationKind::Interval
+        })
+        .map(|annotation| (annotation.start_frame, annotation.end_frame))
+        .collect()
+}
+
+/// Greedily pairs the segments of both annotators by descending IoU.
+fn match_segments(
+    a: &[FrameAnnotation],
+    b: &[FrameAnnotation],
+    label: &str,
+    iou_threshold: f64,
+) -> Vec<SegmentMatch> {
+    let segments_a = segments(a, label);
+    let segments_b = segments(b, label);
+
+    let mut candidates = Vec::new();
+    for (i, segment_a) in segments_a.iter().enumerate() {
+        for (j, segment_b) in segments_b.iter().enumerate() {
+            let iou = segment_iou(*segment_a, *segment_b);
+            if iou >= iou_threshold {
+                candidates.push((iou, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|x, y| y.0.total_cmp(&x.0));
+
+    let mut used_a = vec![false; segments_a.len()];
+    let mut used_b = vec![false; segments_b.len()];
+    let mut matches = Vec::new();
+    for (iou, i, j) in candidates {
+        if used_a[i] || used_b[j] {
+            continue;
+        }
+        used_a[i] = true;
+        used_b[j] = true;
+        matches.push(SegmentMatch {
+            label: label.to_string(),
+            a: Some(segments_a[i]),
+            b: Some(segments_b[j]),
+            iou,
+        });
+    }
+    for (_, segment) in segments_a.iter().enumerate().filter(|(i, _)| !used_a[*i]) {
+        matches.push(SegmentMatch {
+            label: label.to_string(),
+            a: Some(*segment),
+            b: None,
+            iou: 0.0,
+        });
+    }
+    for (_, segment) in segments_b.iter().enumerate().filter(|(j, _)| !used_b[*j]) {
+        matches.push(SegmentMatch {
+            label: label.to_string(),
+            a: None,
+            b: Some(*segment),
+            iou: 0.0,
+        });
+    }
+    matches
+}
+
+fn disagreements(runs: &[Run], labels: &[Label]) -> Vec<Disagreement> {
+    let names = |indexes: &[usize]| {
+        indexes
+            .iter()
+            .map(|index| labels[*index].clone())
+            .collect::<Vec<_>>()
+    };
+    runs.iter()
+        .filter(|run| run.labels_a != run.labels_b)
+        .map(|run| Disagreement {
+            // runs end before `total_frames`, which is at most one past the last u32 frame
+            start_frame: run.start as u32,
+            end_frame: run.end as u32,
+            labels_a: names(&run.labels_a),
+            labels_b: names(&run.labels_b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(start_frame: u32, end_frame: u32) -> FrameAnnotation {
+        FrameAnnotation::new(start_frame, end_frame, "walk".to_string())
+    }
+
+    #[test]
+    fn segment_iou_of_overlapping_segments() {
+        assert_eq!(segment_iou((0, 9), (0, 9)), 1.0);
+        assert!((segment_iou((0, 9), (5, 14)) - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(segment_iou((0, 9), (10, 19)), 0.0);
+        assert_eq!(segment_iou((3, 3), (3, 3)), 1.0);
+        assert!(segment_iou((0, u32::MAX), (0, 0)) > 0.0);
+    }
+
+    #[test]
+    fn kappa_of_half_overlapping_annotations() {
+        // both, only a, only b and neither for 5 frames each: no better than chance
+        let report = compare(&[walk(0, 9)], &[walk(5, 14)], 20, 0.3);
+        let agreement = &report.labels[0];
+        assert_eq!(agreement.label, "walk");
+        assert_eq!(
+            (
+                agreement.frames_a,
+                agreement.frames_b,
+                agreement.frames_both
+            ),
+            (10, 10, 5)
+        );
+        assert!(agreement.kappa.abs() < 1e-9);
+    }
+
+    #[test]
+    fn kappa_of_identical_annotations() {
+        let report = compare(&[walk(0, 9)], &[walk(0, 9)], 20, 0.5);
+        assert!((report.labels[0].kappa - 1.0).abs() < 1e-9);
+        assert!(report.disagreements.is_empty());
+        // every frame labeled by both, the expected agreement is already 1
+        let report = compare(&[walk(0, 19)], &[walk(0, 19)], 20, 0.5);
+        assert_eq!(report.labels[0].kappa, 1.0);
+    }
+
+    #[test]
+    fn kappa_of_opposite_annotations() {
+        let report = compare(&[walk(0, 9)], &[walk(10, 19)], 20, 0.5);
+        assert!((report.labels[0].kappa + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn matches_segments_above_the_threshold() {
+        let report = compare(&[walk(0, 9)], &[walk(5, 14)], 20, 0.3);
+        assert_eq!(report.matches.len(), 1);
+        assert_eq!(report.matches[0].a, Some((0, 9)));
+        assert_eq!(report.matches[0].b, Some((5, 14)));
+
+        let report = compare(&[walk(0, 9)], &[walk(5, 14)], 20, 0.5);
+        let unmatched = report
+            .matches
+            .iter()
+            .map(|found| (found.a, found.b, found.iou))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            unmatched,
+            [(Some((0, 9)), None, 0.0), (None, Some((5, 14)), 0.0)]
+        );
+    }
+
+    #[test]
+    fn matches_the_best_pair_first() {
+        let report = compare(&[walk(0, 9), walk(10, 19)], &[walk(2, 11)], 20, 0.1);
+        let paired = report
+            .matches
+            .iter()
+            .filter(|found| found.a.is_some() && found.b.is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(paired.len(), 1);
+        assert_eq!(paired[0].a, Some((0, 9)));
+    }
+
+    #[test]
+    fn runs_of_disagreement() {
+        let report = compare(&[walk(0, 9)], &[walk(5, 14)], 20, 0.3);
+        let runs = report
+            .disagreements
+            .iter()
+            .map(|run| {
+                (
+                    run.start_frame,
+                    run.end_frame,
+                    run.labels_a.clone(),
+                    run.labels_b.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            [
+                (0, 4, vec!["walk".to_string()], vec![]),
+                (10, 14, vec![], vec!["walk".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn compares_videos_without_annotations() {
+        let report = compare(&[], &[], 20, 0.5);
+        assert!(report.labels.is_empty());
+        assert!(report.disagreements.is_empty());
+    }
+
+    #[test]
+    fn counts_events_with_their_tolerance() {
+        let event = FrameAnnotation::event(10, 2, "walk".to_string());
+        let report = compare(&[event], &[walk(8, 12)], 20, 0.5);
+        assert_eq!(report.labels[0].frames_a, 5);
+        assert_eq!(report.labels[0].frames_both, 5);
+        assert!(report.disagreements.is_empty());
+    }
+
+    #[test]
+    fn compares_segments_up_to_the_last_frame() {
+        let total_frames = u32::MAX as u64 + 1;
+        let report = compare(
+            &[walk(0, u32::MAX)],
+            &[walk(10, u32::MAX)],
+            total_frames,
+            0.5,
+        );
+        let agreement = &report.labels[0];
+        assert_eq!(agreement.frames_a, total_frames);
+        assert_eq!(agreement.frames_both, total_frames - 10);
+        assert_eq!(report.disagreements.len(), 1);
+        assert_eq!(report.disagreements[0].start_frame, 0);
+        assert_eq!(report.disagreements[0].end_frame, 9);
+    }
+}
@@ -1,4 +1,17 @@
-use crate::{project::Project, views};
+use std::{collections::HashMap, fmt, time::Duration};
+
+use crate::{
+    library::{Fingerprinter, VideoLibrary},
+    lock::{self, LockState, ProjectLock},
+    model::Prelabeler,
+    project::Project,
+    settings::Settings,
+    shots::ShotDetector,
+    similarity::{FrameIndex, SimilarFrames},
+    uncertainty::ReviewQueue,
+    utils::{new_id, now, serialize_sorted},
+    views,
+};
 use eframe::egui;
 use serde::{Deserialize, Serialize};
 
@@ -6,6 +19,18 @@ pub struct GlobalState {
     pub annotations: Vec<FrameAnnotation>,
     pub show_export_popup: bool,
     pub project: Option<Project>,
+    pub settings: Settings,
+    pub annotator_filter: Option<String>,
+    pub last_heartbeat: u64,
+    pub library: VideoLibrary,
+    pub fingerprinter: Fingerprinter,
+    pub shots: ShotDetector,
+    pub prelabeler: Prelabeler,
+    /// Uncertain regions the annotator is working through, if a queue was built.
+    pub review_queue: Option<ReviewQueue>,
+    pub frame_index: FrameIndex,
+    /// Last similar-frame search, kept while moving between the videos of its matches.
+    pub similar_frames: Option<SimilarFrames>,
 }
 
 pub struct App {
@@ -13,16 +38,169 @@ pub struct App {
     pub global_state: GlobalState,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FrameAnnotation {
+    #[serde(default)]
+    pub id: String,
     pub start_frame: u32,
     pub end_frame: u32,
     pub label: String,
+    #[serde(default)]
+    pub kind: AnnotationKind,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub attributes: HashMap<String, AttributeValue>,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    #[serde(default)]
+    pub keyframes: Vec<BoxKeyframe>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<u64>,
+    #[serde(default)]
+    pub modified_at: Option<u64>,
+}
+
+/// Bounding box in coordinates normalized to the frame size, `x`/`y` being the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoxKeyframe {
+    pub frame: u32,
+    pub bbox: BoundingBox,
+}
+
+impl BoundingBox {
+    pub fn lerp(&self, other: &BoundingBox, t: f32) -> BoundingBox {
+        BoundingBox {
+            x: self.x + (other.x - self.x) * t,
+            y: self.y + (other.y - self.y) * t,
+            width: self.width + (other.width - self.width) * t,
+            height: self.height + (other.height - self.height) * t,
+        }
+    }
+}
+
+/// Intervals span `start_frame..=end_frame`, events mark the single frame
+/// `start_frame` (== `end_frame`) with an optional tolerance window around it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum AnnotationKind {
+    #[default]
+    Interval,
+    Event {
+        tolerance: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AttributeValue {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeValue::Bool(value) => write!(f, "{}", value),
+            AttributeValue::Number(value) => write!(f, "{}", value),
+            AttributeValue::Text(value) => write!(f, "{}", value),
+        }
+    }
 }
 
 impl FrameAnnotation {
+    pub fn new(start_frame: u32, end_frame: u32, label: String) -> Self {
+        Self {
+            id: new_id(),
+            start_frame,
+            end_frame,
+            label,
+            kind: AnnotationKind::Interval,
+            attributes: HashMap::new(),
+            note: String::new(),
+            confidence: None,
+            keyframes: Vec::new(),
+            author: None,
+            created_at: None,
+            modified_at: None,
+        }
+    }
+
+    pub fn with_author(mut self, author: Option<String>) -> Self {
+        let timestamp = now();
+        self.author = author;
+        self.created_at = Some(timestamp);
+        self.modified_at = Some(timestamp);
+        self
+    }
+
+    pub fn touch(&mut self) {
+        self.modified_at = Some(now());
+    }
+
+    pub fn event(frame: u32, tolerance: u32, label: String) -> Self {
+        Self {
+            kind: AnnotationKind::Event { tolerance },
+            ..Self::new(frame, frame, label)
+        }
+    }
+
+    /// Box at `frame`, linearly interpolated between the surrounding keyframes and held
+    /// constant before the first and after the last keyframe of the segment.
+    pub fn box_at(&self, frame: u32) -> Option<BoundingBox> {
+        if !self.contains(frame) {
+            return None;
+        }
+        let next = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.frame >= frame);
+        match next {
+            None => self.keyframes.last().map(|keyframe| keyframe.bbox),
+            Some(0) => self.keyframes.first().map(|keyframe| keyframe.bbox),
+            Some(i) => {
+                let previous = &self.keyframes[i - 1];
+                let next = &self.keyframes[i];
+                let t = (frame - previous.frame) as f32 / (next.frame - previous.frame) as f32;
+                Some(previous.bbox.lerp(&next.bbox, t))
+            }
+        }
+    }
+
+    pub fn set_keyframe(&mut self, frame: u32, bbox: BoundingBox) {
+        self.keyframes.retain(|keyframe| keyframe.frame != frame);
+        self.keyframes.push(BoxKeyframe { frame, bbox });
+        self.keyframes.sort_by_key(|keyframe| keyframe.frame);
+    }
+
+    /// Segments created between shot cuts have no label until the annotator gives them one,
+    /// and don't count as labeled frames until then.
+    pub fn is_labeled(&self) -> bool {
+        !self.label.is_empty()
+    }
+
+    pub fn is_event(&self) -> bool {
+        matches!(self.kind, AnnotationKind::Event { .. })
+    }
+
     pub fn contains(&self, frame: u32) -> bool {
-        frame >= self.start_frame && frame <= self.end_frame
+        match self.kind {
+            AnnotationKind::Interval => frame >= self.start_frame && frame <= self.end_frame,
+            AnnotationKind::Event { tolerance } => {
+                frame.saturating_add(tolerance) >= self.start_frame
+                    && frame <= self.start_frame.saturating_add(tolerance)
+            }
+        }
     }
 }
 
@@ -34,6 +212,16 @@ impl App {
                 annotations: Vec::new(),
                 show_export_popup: false,
                 project: None,
+                settings: Settings::load(),
+                annotator_filter: None,
+                last_heartbeat: 0,
+                library: VideoLibrary::default(),
+                fingerprinter: Fingerprinter::default(),
+                shots: ShotDetector::default(),
+                prelabeler: Prelabeler::default(),
+                review_queue: None,
+                frame_index: FrameIndex::default(),
+                similar_frames: None,
             },
         }
     }
@@ -41,6 +229,31 @@ impl App {
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(project) = &self.global_state.project {
+            if !project.is_read_only()
+                && now() >= self.global_state.last_heartbeat + lock::HEARTBEAT_INTERVAL
+            {
+                lock::heartbeat(&project.path);
+                self.global_state.last_heartbeat = now();
+            }
+            ctx.request_repaint_after(Duration::from_secs(lock::HEARTBEAT_INTERVAL));
+        }
+        if let Some(project) = self.global_state.project.as_mut() {
+            project
+                .shot_cuts
+                .extend(self.global_state.shots.take_finished());
+            if let Some(fingerprints) = self.global_state.fingerprinter.take_finished() {
+                project.relink_videos(fingerprints);
+            }
+            for (video, proposals, scores) in self.global_state.prelabeler.take_finished() {
+                if let Err(e) = scores.write(&project.model_scores_path(&video)) {
+                    eprintln!("Failed to write scores of {}: {}", video, e);
+                }
+                // a new run replaces the proposals left over from the previous one
+                project.proposals.insert(video, proposals);
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |_ui| {
             if let Some(next_view) = self.current_view.render(ctx, &mut self.global_state) {
                 self.current_view = next_view;
@@ -49,8 +262,100 @@ impl eframe::App for App {
     }
 }
 
+impl Drop for App {
+    fn drop(&mut self) {
+        if let Some(project) = &self.global_state.project {
+            lock::release(&project.path);
+        }
+    }
+}
+
 impl GlobalState {
     pub fn used_labels(&self) -> Vec<String> {
         self.annotations.iter().map(|a| a.label.clone()).collect()
     }
+
+    pub fn matches_annotator_filter(&self, annotation: &FrameAnnotation) -> bool {
+        self.annotator_filter.as_ref().map_or(true, |annotator| {
+            annotation.author.as_ref() == Some(annotator)
+        })
+    }
+
+    /// Name written into lock files, the configured annotator or the system user.
+    pub fn lock_holder(&self) -> String {
+        self.settings
+            .annotator()
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .unwrap_or("unknown".to_string())
+    }
+
+    /// Starts fingerprinting the project's new videos, renamed and missing videos are
+    /// looked for once that's done.
+    pub fn rescan_videos(&mut self, ctx: &egui::Context) {
+        if let Some(project) = &self.project {
+            self.fingerprinter
+                .request(project.unfingerprinted_videos(), ctx);
+        }
+    }
+
+    /// Opens `project`, taking its lock or opening it read-only if someone else holds it.
+    /// Returns the lock if it is stale and could be taken over.
+    pub fn open_project(&mut self, mut project: Project) -> Option<ProjectLock> {
+        if let Some(previous) = self.project.take() {
+            lock::release(&previous.path);
+        }
+        project.path_variables = self.settings.path_variables.clone();
+        self.library = VideoLibrary::default();
+        self.fingerprinter = Fingerprinter::default();
+        self.shots = ShotDetector::default();
+        self.prelabeler = Prelabeler::default();
+        self.review_queue = None;
+        self.frame_index = FrameIndex::default();
+        self.similar_frames = None;
+        let stale_lock = match lock::try_acquire(&project.path, &self.lock_holder()) {
+            LockState::Acquired => None,
+            LockState::HeldByOther(lock) => {
+                project.locked_by = Some(lock);
+                None
+            }
+            LockState::Stale(lock) => {
+                project.locked_by = Some(lock.clone());
+                Some(lock)
+            }
+            LockState::Failed(e) => {
+                project.lock_error = Some(e.to_string());
+                None
+            }
+        };
+        self.last_heartbeat = now();
+        self.project = Some(project);
+        stale_lock
+    }
+
+    /// Takes over a stale lock of the current project.
+    pub fn take_over_lock(&mut self) {
+        let holder = self.lock_holder();
+        if let Some(project) = self.project.as_mut() {
+            if let Err(e) = lock::acquire(&project.path, &holder) {
+                project.lock_error = Some(e.to_string());
+                return;
+            }
+            project.locked_by = None;
+            self.last_heartbeat = now();
+        }
+    }
+
+    pub fn annotators(&self) -> Vec<String> {
+        let mut annotators = self
+            .project
+            .iter()
+            .flat_map(|project| project.annotations.values())
+            .flatten()
+            .filter_map(|annotation| annotation.author.clone())
+            .collect::<Vec<_>>();
+        annotators.sort();
+        annotators.dedup();
+        annotators
+    }
 }
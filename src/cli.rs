@@ -0,0 +1,364 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    evaluation::{evaluate, parse_thresholds, read_predictions},
+    lock::{acquire, release, try_acquire, LockState},
+    merge::{merge, resolve, Resolution},
+    project::{read_config, write_config, Project, StorageLayout},
+};
+
+/// Runs a command line subcommand. Returns `None` if the arguments are not a known
+/// subcommand, in which case the GUI is started.
+pub fn run(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some("merge") => Some(merge_command(&args[1..])),
+        Some("convert") => Some(convert_command(&args[1..])),
+        Some("query") => Some(query_command(&args[1..])),
+        Some("import-scores") => Some(import_scores_command(&args[1..])),
+        Some("evaluate") => Some(evaluate_command(&args[1..])),
+        _ => None,
+    }
+}
+
+/// Reads the project at `path` with all its annotations, reporting why if it can't be read.
+fn read_project(path: &PathBuf) -> Option<Project> {
+    match read_config(path) {
+        Ok(config) => Some(Project::from(config, path.clone())),
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Takes the lock of the project at `path` for `command`, taking over a stale lock.
+/// Returns `false` after reporting why if the project can't be locked.
+fn lock_project(path: &Path, command: &str) -> bool {
+    match try_acquire(path, command) {
+        LockState::HeldByOther(lock) => {
+            eprintln!(
+                "{} is locked by {} on {}, close it there first",
+                path.display(),
+                lock.holder,
+                lock.host
+            );
+            false
+        }
+        LockState::Stale(lock) => {
+            eprintln!(
+                "Taking over the stale lock of {} on {}",
+                lock.holder, lock.host
+            );
+            if let Err(e) = acquire(path, command) {
+                eprintln!("Failed to lock {}: {}", path.display(), e);
+                return false;
+            }
+            true
+        }
+        LockState::Failed(e) => {
+            eprintln!("Failed to lock {}: {}", path.display(), e);
+            false
+        }
+        LockState::Acquired => true,
+    }
+}
+
+fn merge_command(args: &[String]) -> i32 {
+    let mut paths = Vec::new();
+    let mut output = None;
+    let mut prefer = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output = args.next().map(PathBuf::from),
+            "--prefer" => {
+                prefer = match args.next().map(String::as_str) {
+                    Some("ours") => Some(Resolution::Ours),
+                    Some("theirs") => Some(Resolution::Theirs),
+                    Some("both") => Some(Resolution::Both),
+                    _ => {
+                        eprintln!("--prefer expects ours, theirs or both");
+                        return 2;
+                    }
+                }
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.len() != 3 {
+        eprintln!(
+            "usage: merge <base> <ours> <theirs> [--output <project folder>] [--prefer ours|theirs|both]"
+        );
+        return 2;
+    }
+
+    let mut configs = Vec::new();
+    for path in &paths {
+        match read_config(path) {
+            Ok(config) => configs.push(config),
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                return 1;
+            }
+        }
+    }
+
+    let mut result = merge(&configs[0], &configs[1], &configs[2]);
+    for conflict in &result.setting_conflicts {
+        eprintln!("both sides changed the {}, kept ours", conflict.describe());
+    }
+    for conflict in &result.conflicts {
+        let describe = |annotation: &Option<crate::app::FrameAnnotation>| {
+            annotation
+                .as_ref()
+                .map_or("deleted".to_string(), |annotation| {
+                    format!(
+                        "{} {} - {}",
+                        annotation.label, annotation.start_frame, annotation.end_frame
+                    )
+                })
+        };
+        eprintln!(
+            "conflict in {}: ours {}, theirs {}",
+            conflict.video,
+            describe(&conflict.ours),
+            describe(&conflict.theirs)
+        );
+    }
+    if !result.conflicts.is_empty() {
+        let Some(resolution) = prefer else {
+            eprintln!(
+                "{} conflicts, resolve them in the app or pass --prefer",
+                result.conflicts.len()
+            );
+            return 1;
+        };
+        for conflict in result.conflicts.clone() {
+            resolve(&mut result.config, &conflict, resolution);
+        }
+    }
+
+    let output = output.unwrap_or_else(|| {
+        let ours = paths[1].clone();
+        if ours.is_dir() {
+            ours
+        } else {
+            ours.parent().map(PathBuf::from).unwrap_or_default()
+        }
+    });
+    if !lock_project(&output, "merge") {
+        return 1;
+    }
+    let written = write_config(&output, &result.config);
+    release(&output);
+    if let Err(e) = written {
+        eprintln!("Failed to write {}: {}", output.display(), e);
+        return 1;
+    }
+    println!("Merged into {}", output.join("project.json").display());
+    0
+}
+
+fn convert_command(args: &[String]) -> i32 {
+    let layout = match args.get(1).map(String::as_str) {
+        Some("single") => StorageLayout::SingleFile,
+        Some("per-video") => StorageLayout::PerVideo,
+        Some("sqlite") => StorageLayout::Sqlite,
+        _ => {
+            eprintln!("usage: convert <project folder> single|per-video|sqlite");
+            return 2;
+        }
+    };
+    let path = PathBuf::from(&args[0]);
+    if !lock_project(&path, "convert") {
+        return 1;
+    }
+    let Some(mut project) = read_project(&path) else {
+        release(&path);
+        return 1;
+    };
+    let converted = project.convert_storage(layout);
+    release(&path);
+    if let Err(e) = converted {
+        eprintln!("Failed to convert {}: {}", path.display(), e);
+        return 1;
+    }
+    println!("Converted {}", path.display());
+    0
+}
+
+fn query_command(args: &[String]) -> i32 {
+    let mut path = None;
+    let mut label = None;
+    let mut frames = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--label" => label = args.next().cloned(),
+            "--frames" => {
+                let range = args.next().and_then(|range| {
+                    let (start, end) = range.split_once('-')?;
+                    Some((start.parse().ok()?, end.parse().ok()?))
+                });
+                if range.is_none() {
+                    eprintln!("--frames expects <start>-<end>");
+                    return 2;
+                }
+                frames = range;
+            }
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    let Some(path) = path.filter(|path| path.join("project.json").exists()) else {
+        eprintln!("usage: query <project folder> [--label <label>] [--frames <start>-<end>]");
+        return 2;
+    };
+
+    let Some(project) = read_project(&path) else {
+        return 1;
+    };
+    match project.query(label.as_deref(), frames) {
+        Ok(matches) => {
+            for (video, annotation) in matches {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    video,
+                    annotation.start_frame,
+                    annotation.end_frame,
+                    annotation.label,
+                    annotation.id
+                );
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to query {}: {}", path.display(), e);
+            1
+        }
+    }
+}
+
+fn import_scores_command(args: &[String]) -> i32 {
+    let [path, video, file] = args else {
+        eprintln!("usage: import-scores <project folder> <video> <scores csv>");
+        return 2;
+    };
+    let path = PathBuf::from(path);
+    if !path.join("project.json").exists() {
+        eprintln!("{} is not a project folder", path.display());
+        return 2;
+    }
+
+    let Some(project) = read_project(&path) else {
+        return 1;
+    };
+    match project.import_scores(video, &PathBuf::from(file)) {
+        Ok(scores) => {
+            println!(
+                "Imported {} rows of {} classes for {}",
+                scores.frames.len(),
+                scores.classes.len(),
+                video
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to import {}: {}", file, e);
+            1
+        }
+    }
+}
+
+fn evaluate_command(args: &[String]) -> i32 {
+    let mut paths = Vec::new();
+    let mut thresholds = vec![0.3, 0.5, 0.7];
+    let mut output = None;
+    let mut annotator = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--annotator" => annotator = args.next().cloned(),
+            "--iou" => match args.next().and_then(|input| parse_thresholds(input)) {
+                Some(parsed) => thresholds = parsed,
+                None => {
+                    eprintln!("--iou expects thresholds like 0.3,0.5,0.7");
+                    return 2;
+                }
+            },
+            "--output" => output = args.next().map(PathBuf::from),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.len() < 2 || !paths[0].join("project.json").exists() {
+        eprintln!(
+            "usage: evaluate <project folder> <predictions csv>... [--annotator <name>] [--iou 0.3,0.5,0.7] [--output <report json>]"
+        );
+        return 2;
+    }
+
+    let mut predictions = Vec::new();
+    for path in &paths[1..] {
+        match read_predictions(path) {
+            Ok(read) => predictions.extend(read),
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                return 1;
+            }
+        }
+    }
+    let Some(project) = read_project(&paths[0]) else {
+        return 1;
+    };
+    let report = evaluate(
+        &project.annotations,
+        annotator.as_deref(),
+        &predictions,
+        &thresholds,
+    );
+
+    match &report.annotator {
+        Some(annotator) => println!("ground truth: annotations of {}", annotator),
+        None if report.merged_annotators > 1 => println!(
+            "ground truth: annotations of {} annotators merged, pass --annotator to score against one",
+            report.merged_annotators
+        ),
+        None => {}
+    }
+    println!("{} videos", report.videos.len());
+    println!("label\tprecision\trecall\tf1");
+    for label in &report.labels {
+        println!(
+            "{}\t{:.3}\t{:.3}\t{:.3}",
+            label.label, label.precision, label.recall, label.f1
+        );
+    }
+    for (threshold, map) in report
+        .iou_thresholds
+        .iter()
+        .zip(&report.mean_average_precision)
+    {
+        println!("mAP@{:.2}\t{:.3}", threshold, map);
+    }
+    println!("worst errors:");
+    for error in &report.worst_errors {
+        println!(
+            "{}\t{}-{}\t{}\t{}\tIoU {:.2}",
+            error.video,
+            error.start_frame,
+            error.end_frame,
+            error.label,
+            error.kind.name(),
+            error.iou
+        );
+    }
+    if let Some(output) = output {
+        let written = serde_json::to_string_pretty(&report)
+            .map_err(std::io::Error::from)
+            .and_then(|content| std::fs::write(&output, content));
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", output.display(), e);
+            return 1;
+        }
+    }
+    0
+}
@@ -0,0 +1,773 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::{
+    agreement::segment_iou,
+    app::{AnnotationKind, FrameAnnotation},
+    project::{Label, Video},
+    utils::parse_csv,
+};
+
+/// IoU a predicted segment needs to count as finding a ground truth segment when
+/// listing the worst errors.
+pub const ERROR_IOU: f64 = 0.5;
+
+/// Number of segments listed as the worst errors.
+pub const WORST_ERRORS: usize = 25;
+
+/// Name of the "no label" row and column of the confusion matrix.
+pub const NO_LABEL: &str = "(none)";
+
+/// A predicted segment, per-frame predictions are merged into segments when read.
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    pub video: Video,
+    pub label: Label,
+    pub start_frame: u32,
+    pub end_frame: u32,
+    pub score: f64,
+}
+
+/// Frame-level counts and scores of a single label.
+#[derive(Debug, Clone, Serialize)]
+pub struct LabelMetrics {
+    pub label: Label,
+    pub true_positives: u32,
+    pub false_positives: u32,
+    pub false_negatives: u32,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ErrorKind {
+    /// No prediction overlaps the ground truth segment.
+    Missed,
+    /// A prediction overlaps the ground truth segment, but not enough.
+    Mislocated,
+    /// The predicted segment overlaps no ground truth segment.
+    FalsePositive,
+}
+
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Missed => "missed",
+            ErrorKind::Mislocated => "mislocated",
+            ErrorKind::FalsePositive => "false positive",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentError {
+    pub video: Video,
+    pub label: Label,
+    pub start_frame: u32,
+    pub end_frame: u32,
+    pub kind: ErrorKind,
+    /// Best IoU with a segment of the same label on the other side.
+    pub iou: f64,
+    /// Roughly how many frames the error costs, used to rank errors.
+    pub wrong_frames: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvaluationReport {
+    /// Annotator whose annotations are the ground truth, `None` if every annotator's
+    /// annotations are merged.
+    pub annotator: Option<String>,
+    /// Number of annotators whose annotations were merged into the ground truth, the
+    /// scores can't reach 1 when they disagree.
+    pub merged_annotators: usize,
+    /// Videos that had predictions and were evaluated.
+    pub videos: Vec<Video>,
+    pub labels: Vec<LabelMetrics>,
+    /// Labels of the confusion matrix rows and columns, ending with [`NO_LABEL`].
+    pub confusion_labels: Vec<Label>,
+    /// Frame counts by ground truth label (rows) and predicted label (columns).
+    pub confusion: Vec<Vec<u32>>,
+    pub iou_thresholds: Vec<f64>,
+    /// Average precision of each label with ground truth segments, per IoU threshold.
+    pub average_precision: Vec<(Label, Vec<f64>)>,
+    /// Mean of the average precisions per IoU threshold.
+    pub mean_average_precision: Vec<f64>,
+    pub worst_errors: Vec<SegmentError>,
+}
+
+/// Reads a CSV of predicted segments (`video,label,start_frame,end_frame`) or per-frame
+/// predictions (`video,frame,label`), either with an optional `score` column. Frames are
+/// numbered like in the app, starting at 1.
+pub fn read_predictions(path: &Path) -> Result<Vec<Prediction>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut records = parse_csv(&content).into_iter();
+    let (_, header) = records.next().ok_or("the prediction file is empty")?;
+    let columns = header
+        .iter()
+        .map(|column| column.to_lowercase())
+        .collect::<Vec<_>>();
+    let column = |name: &str| columns.iter().position(|column| column == name);
+    let (Some(video_column), Some(label_column)) = (column("video"), column("label")) else {
+        return Err("the prediction file needs `video` and `label` columns".to_string());
+    };
+    let score_column = column("score").or_else(|| column("confidence"));
+    let frame_columns = match (column("start_frame"), column("end_frame"), column("frame")) {
+        (Some(start), Some(end), _) => (start, end),
+        (_, _, Some(frame)) => (frame, frame),
+        _ => {
+            return Err(
+                "the prediction file needs `start_frame` and `end_frame` or `frame` columns"
+                    .to_string(),
+            )
+        }
+    };
+
+    let mut predictions = Vec::new();
+    for (line, fields) in records {
+        let field = |index: usize| fields.get(index).map_or("", String::as_str);
+        let frame = |index: usize| {
+            field(index)
+                .parse::<u32>()
+                .map_err(|_| format!("line {}: invalid frame", line))
+        };
+        let score = match score_column {
+            Some(index) => field(index)
+                .parse::<f64>()
+                .map_err(|_| format!("line {}: invalid score", line))?,
+            None => 1.0,
+        };
+        let (start_frame, end_frame) = (frame(frame_columns.0)?, frame(frame_columns.1)?);
+        if start_frame > end_frame {
+            return Err(format!("line {}: start_frame is after end_frame", line));
+        }
+        predictions.push(Prediction {
+            video: field(video_column).to_string(),
+            label: field(label_column).to_string(),
+            start_frame,
+            end_frame,
+            score,
+        });
+    }
+    if frame_columns.0 == frame_columns.1 {
+        predictions = merge_frames(predictions);
+    }
+    Ok(predictions)
+}
+
+/// Merges per-frame predictions of consecutive frames with the same label into segments
+/// scored by their mean score.
+fn merge_frames(mut predictions: Vec<Prediction>) -> Vec<Prediction> {
+    predictions.sort_by(|a, b| {
+        (&a.video, &a.label, a.start_frame).cmp(&(&b.video, &b.label, b.start_frame))
+    });
+    let mut segments: Vec<(Prediction, u32)> = Vec::new();
+    for prediction in predictions {
+        if let Some((last, count)) = segments.last_mut() {
+            if last.video == prediction.video
+                && last.label == prediction.label
+                && last.end_frame.saturating_add(1) >= prediction.start_frame
+            {
+                last.end_frame = last.end_frame.max(prediction.end_frame);
+                last.score += prediction.score;
+                *count += 1;
+                continue;
+            }
+        }
+        segments.push((prediction, 1));
+    }
+    segments
+        .into_iter()
+        .map(|(mut segment, count)| {
+            segment.score /= count as f64;
+            segment
+        })
+        .collect()
+}
+
+/// Scores `predictions` against the interval annotations of the videos they cover, only
+/// the annotations of `annotator` if given.
+pub fn evaluate(
+    truth: &HashMap<Video, Vec<FrameAnnotation>>,
+    annotator: Option<&str>,
+    predictions: &[Prediction],
+    iou_thresholds: &[f64],
+) -> EvaluationReport {
+    let videos = predictions
+        .iter()
+        .map(|prediction| prediction.video.clone())
+        .collect::<BTreeSet<_>>();
+    let annotations = videos
+        .iter()
+        .flat_map(|video| {
+            truth
+                .get(video)
+                .into_iter()
+                .flatten()
+                .filter(|annotation| {
+                    annotation.kind == AnnotationKind::Interval
+                        && annotation.is_labeled()
+                        && annotator.map_or(true, |annotator| {
+                            annotation.author.as_deref() == Some(annotator)
+                        })
+                })
+                .map(move |annotation| (video, annotation))
+        })
+        .collect::<Vec<_>>();
+    let merged_annotators = annotations
+        .iter()
+        .map(|(_, annotation)| &annotation.author)
+        .collect::<BTreeSet<_>>()
+        .len();
+    let truth = annotations
+        .into_iter()
+        .map(|(video, annotation)| Prediction {
+            video: video.clone(),
+            label: annotation.label.clone(),
+            start_frame: annotation.start_frame,
+            end_frame: annotation.end_frame,
+            score: 1.0,
+        })
+        .collect::<Vec<_>>();
+    let labels = truth
+        .iter()
+        .chain(predictions)
+        .map(|segment| segment.label.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let (label_metrics, confusion) = frame_metrics(&videos, &labels, &truth, predictions);
+    let average_precision = labels
+        .iter()
+        .filter(|label| truth.iter().any(|segment| &segment.label == *label))
+        .map(|label| {
+            let precisions = iou_thresholds
+                .iter()
+                .map(|threshold| label_average_precision(&truth, predictions, label, *threshold))
+                .collect();
+            (label.clone(), precisions)
+        })
+        .collect::<Vec<(Label, Vec<f64>)>>();
+    let mean_average_precision = (0..iou_thresholds.len())
+        .map(|i| {
+            average_precision
+                .iter()
+                .map(|(_, precisions)| precisions[i])
+                .sum::<f64>()
+                / average_precision.len().max(1) as f64
+        })
+        .collect();
+
+    let mut confusion_labels = labels.clone();
+    confusion_labels.push(NO_LABEL.to_string());
+    EvaluationReport {
+        annotator: annotator.map(str::to_string),
+        merged_annotators,
+        videos: videos.into_iter().collect(),
+        labels: label_metrics,
+        confusion_labels,
+        confusion,
+        iou_thresholds: iou_thresholds.to_vec(),
+        average_precision,
+        mean_average_precision,
+        worst_errors: worst_errors(&truth, predictions),
+    }
+}
+
+/// Per-label frame counts and the confusion matrix, over the frames where either side
+/// has a label.
+///
+/// Works on the spans between segment boundaries rather than on single frames, so that
+/// the cost doesn't depend on how far the frame numbers go.
+fn frame_metrics(
+    videos: &BTreeSet<Video>,
+    labels: &[Label],
+    truth: &[Prediction],
+    predictions: &[Prediction],
+) -> (Vec<LabelMetrics>, Vec<Vec<u32>>) {
+    let none = labels.len();
+    let mut counts = vec![(0u32, 0u32, 0u32); labels.len()];
+    let mut confusion = vec![vec![0u32; labels.len() + 1]; labels.len() + 1];
+    for video in videos {
+        // (frame, side, label, change of the number of segments covering the frame),
+        // side 0 is the ground truth and 1 the predictions
+        let mut boundaries = Vec::new();
+        for (side, segments) in [truth, predictions].into_iter().enumerate() {
+            for segment in segments.iter().filter(|segment| &segment.video == video) {
+                let label = labels.binary_search(&segment.label).unwrap();
+                boundaries.push((segment.start_frame as u64, side, label, 1));
+                boundaries.push((segment.end_frame as u64 + 1, side, label, -1));
+            }
+        }
+        boundaries.sort_unstable();
+
+        let mut covering = [vec![0i32; labels.len()], vec![0i32; labels.len()]];
+        let mut previous = 0;
+        for (frame, side, label, change) in boundaries {
+            let frames = u32::try_from(frame - previous).unwrap_or(u32::MAX);
+            previous = frame;
+            if frames > 0 {
+                let [truth, predicted] = &covering;
+                for (label, count) in counts.iter_mut().enumerate() {
+                    let field = match (truth[label] > 0, predicted[label] > 0) {
+                        (true, true) => &mut count.0,
+                        (false, true) => &mut count.1,
+                        (true, false) => &mut count.2,
+                        (false, false) => continue,
+                    };
+                    *field = field.saturating_add(frames);
+                }
+                let set = |covering: &[i32]| {
+                    let set = (0..labels.len())
+                        .filter(|label| covering[*label] > 0)
+                        .collect::<Vec<_>>();
+                    if set.is_empty() {
+                        vec![none]
+                    } else {
+                        set
+                    }
+                };
+                for row in set(truth) {
+                    for column in set(predicted) {
+                        if row != none || column != none {
+                            confusion[row][column] = confusion[row][column].saturating_add(frames);
+                        }
+                    }
+                }
+            }
+            covering[side][label] += change;
+        }
+    }
+
+    let metrics = labels
+        .iter()
+        .zip(counts)
+        .map(
+            |(label, (true_positives, false_positives, false_negatives))| {
+                let ratio = |a: u32, b: u32| {
+                    if a == 0 && b == 0 {
+                        0.0
+                    } else {
+                        a as f64 / (a as f64 + b as f64)
+                    }
+                };
+                let precision = ratio(true_positives, false_positives);
+                let recall = ratio(true_positives, false_negatives);
+                let f1 = if precision + recall == 0.0 {
+                    0.0
+                } else {
+                    2.0 * precision * recall / (precision + recall)
+                };
+                LabelMetrics {
+                    label: label.clone(),
+                    true_positives,
+                    false_positives,
+                    false_negatives,
+                    precision,
+                    recall,
+                    f1,
+                }
+            },
+        )
+        .collect();
+    (metrics, confusion)
+}
+
+fn iou(a: &Prediction, b: &Prediction) -> f64 {
+    if a.video != b.video || a.label != b.label {
+        return 0.0;
+    }
+    segment_iou((a.start_frame, a.end_frame), (b.start_frame, b.end_frame))
+}
+
+/// Area under the precision/recall curve of `label`, predictions matching the best
+/// unmatched ground truth segment in descending score order.
+fn label_average_precision(
+    truth: &[Prediction],
+    predictions: &[Prediction],
+    label: &str,
+    iou_threshold: f64,
+) -> f64 {
+    let truth = truth
+        .iter()
+        .filter(|segment| segment.label == label)
+        .collect::<Vec<_>>();
+    let mut predictions = predictions
+        .iter()
+        .filter(|segment| segment.label == label)
+        .collect::<Vec<_>>();
+    predictions.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut matched = vec![false; truth.len()];
+    let mut true_positives = 0;
+    let mut curve = Vec::new();
+    for (rank, prediction) in predictions.iter().enumerate() {
+        let best = truth
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !matched[*i])
+            .map(|(i, segment)| (i, iou(prediction, segment)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, overlap)) = best {
+            if overlap >= iou_threshold {
+                matched[i] = true;
+                true_positives += 1;
+            }
+        }
+        curve.push((
+            true_positives as f64 / truth.len() as f64,
+            true_positives as f64 / (rank + 1) as f64,
+        ));
+    }
+
+    // all-point interpolation, precision at a recall is the best precision at any higher recall
+    let mut average_precision = 0.0;
+    let mut previous_recall = 0.0;
+    for (i, (recall, _)) in curve.iter().enumerate() {
+        if *recall > previous_recall {
+            let precision = curve[i..]
+                .iter()
+                .map(|(_, precision)| *precision)
+                .fold(0.0, f64::max);
+            average_precision += (recall - previous_recall) * precision;
+            previous_recall = *recall;
+        }
+    }
+    average_precision
+}
+
+fn worst_errors(truth: &[Prediction], predictions: &[Prediction]) -> Vec<SegmentError> {
+    let best_iou = |segment: &Prediction, others: &[Prediction]| {
+        others
+            .iter()
+            .map(|other| iou(segment, other))
+            .fold(0.0, f64::max)
+    };
+    let length = |segment: &Prediction| (segment.end_frame - segment.start_frame) as f64 + 1.0;
+    let error = |segment: &Prediction, kind, iou: f64| SegmentError {
+        video: segment.video.clone(),
+        label: segment.label.clone(),
+        start_frame: segment.start_frame,
+        end_frame: segment.end_frame,
+        kind,
+        iou,
+        wrong_frames: (length(segment) * (1.0 - iou)).round() as u32,
+    };
+
+    let mut errors = Vec::new();
+    for segment in truth {
+        let iou = best_iou(segment, predictions);
+        if iou == 0.0 {
+            errors.push(error(segment, ErrorKind::Missed, iou));
+        } else if iou < ERROR_IOU {
+            errors.push(error(segment, ErrorKind::Mislocated, iou));
+        }
+    }
+    for segment in predictions {
+        let iou = best_iou(segment, truth);
+        if iou == 0.0 {
+            errors.push(error(segment, ErrorKind::FalsePositive, iou));
+        }
+    }
+    errors.sort_by(|a, b| b.wrong_frames.cmp(&a.wrong_frames));
+    errors.truncate(WORST_ERRORS);
+    errors
+}
+
+/// Parses comma separated IoU thresholds like `0.3, 0.5, 0.7`.
+pub fn parse_thresholds(input: &str) -> Option<Vec<f64>> {
+    input
+        .split(',')
+        .map(|threshold| {
+            threshold
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|threshold| *threshold > 0.0 && *threshold <= 1.0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(
+        video: &str,
+        label: &str,
+        start_frame: u32,
+        end_frame: u32,
+        score: f64,
+    ) -> Prediction {
+        Prediction {
+            video: video.to_string(),
+            label: label.to_string(),
+            start_frame,
+            end_frame,
+            score,
+        }
+    }
+
+    fn read(name: &str, content: &str) -> Result<Vec<Prediction>, String> {
+        let path = std::env::temp_dir().join(format!(
+            "classroom_predictions_{}_{}.csv",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, content).unwrap();
+        let predictions = read_predictions(&path);
+        std::fs::remove_file(&path).unwrap();
+        predictions
+    }
+
+    #[test]
+    fn reads_segments() {
+        let predictions = read(
+            "segments",
+            "\"Video\",label,start_frame,end_frame,score\na.mp4,walk,10,29,0.9\n\na.mp4,\"run\",40,59,0.5\n",
+        )
+        .unwrap();
+        assert_eq!(predictions.len(), 2);
+        assert_eq!(predictions[0].video, "a.mp4");
+        assert_eq!(predictions[0].label, "walk");
+        assert_eq!(
+            (predictions[0].start_frame, predictions[0].end_frame),
+            (10, 29)
+        );
+        assert_eq!(predictions[0].score, 0.9);
+        assert_eq!(predictions[1].label, "run");
+    }
+
+    #[test]
+    fn reads_frames_into_segments() {
+        let predictions = read(
+            "frames",
+            "video,frame,label,confidence\na.mp4,3,walk,0.6\na.mp4,1,walk,0.2\na.mp4,2,walk,0.4\na.mp4,5,walk,1\n",
+        )
+        .unwrap();
+        assert_eq!(predictions.len(), 2);
+        assert_eq!(
+            (predictions[0].start_frame, predictions[0].end_frame),
+            (1, 3)
+        );
+        assert!((predictions[0].score - 0.4).abs() < 1e-9);
+        assert_eq!(
+            (predictions[1].start_frame, predictions[1].end_frame),
+            (5, 5)
+        );
+    }
+
+    #[test]
+    fn reads_quoted_fields() {
+        let predictions = read(
+            "quoted",
+            "video,label,frame\n\"clips/a, b.mp4\",\"walk, slow\",7\n",
+        )
+        .unwrap();
+        assert_eq!(predictions[0].video, "clips/a, b.mp4");
+        assert_eq!(predictions[0].label, "walk, slow");
+    }
+
+    #[test]
+    fn defaults_the_score_to_one() {
+        let predictions = read("no_score", "video,label,frame\na.mp4,walk,7\n").unwrap();
+        assert_eq!(predictions[0].score, 1.0);
+    }
+
+    #[test]
+    fn rejects_invalid_predictions() {
+        assert!(read("empty", "\n").is_err());
+        assert!(read("no_frames", "video,label\na.mp4,walk\n").is_err());
+        assert!(read("no_label", "video,frame\na.mp4,1\n").is_err());
+        assert!(read(
+            "reversed",
+            "video,label,start_frame,end_frame\na.mp4,walk,9,3\n"
+        )
+        .is_err());
+        assert!(read("bad_frame", "video,label,frame\na.mp4,walk,-1\n").is_err());
+        assert!(read("bad_score", "video,label,frame,score\na.mp4,walk,1,high\n").is_err());
+    }
+
+    #[test]
+    fn merges_consecutive_frames_of_the_same_label() {
+        let segments = merge_frames(vec![
+            segment("a.mp4", "walk", 2, 2, 0.5),
+            segment("a.mp4", "run", 3, 3, 1.0),
+            segment("a.mp4", "walk", 1, 1, 0.1),
+            segment("a.mp4", "walk", 3, 3, 0.3),
+            segment("b.mp4", "walk", 4, 4, 1.0),
+            segment("a.mp4", "walk", 4, 4, 0.7),
+            segment("a.mp4", "walk", 6, 6, 1.0),
+        ]);
+        let ranges = segments
+            .iter()
+            .map(|s| {
+                (
+                    s.video.as_str(),
+                    s.label.as_str(),
+                    s.start_frame,
+                    s.end_frame,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            [
+                ("a.mp4", "run", 3, 3),
+                ("a.mp4", "walk", 1, 4),
+                ("a.mp4", "walk", 6, 6),
+                ("b.mp4", "walk", 4, 4),
+            ]
+        );
+        assert!((segments[1].score - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn merges_frames_at_the_end_of_the_range() {
+        let segments = merge_frames(vec![
+            segment("a.mp4", "walk", u32::MAX, u32::MAX, 1.0),
+            segment("a.mp4", "walk", u32::MAX - 1, u32::MAX - 1, 1.0),
+        ]);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].start_frame, u32::MAX - 1);
+    }
+
+    #[test]
+    fn average_precision() {
+        let truth = [
+            segment("a.mp4", "walk", 0, 9, 1.0),
+            segment("a.mp4", "walk", 20, 29, 1.0),
+            segment("a.mp4", "run", 40, 49, 1.0),
+        ];
+        let predictions = [
+            segment("a.mp4", "walk", 0, 9, 0.9),
+            segment("a.mp4", "walk", 40, 49, 0.8),
+            // IoU 0.5 with the second segment
+            segment("a.mp4", "walk", 20, 24, 0.7),
+        ];
+        // recall/precision 0.5/1, 0.5/0.5 and 1/0.67
+        let ap = label_average_precision(&truth, &predictions, "walk", 0.5);
+        assert!((ap - (0.5 + 0.5 * 2.0 / 3.0)).abs() < 1e-9);
+        let ap = label_average_precision(&truth, &predictions, "walk", 0.7);
+        assert!((ap - 0.5).abs() < 1e-9);
+        assert_eq!(
+            label_average_precision(&truth, &predictions, "run", 0.5),
+            0.0
+        );
+    }
+
+    #[test]
+    fn average_precision_matches_each_segment_once() {
+        let truth = [segment("a.mp4", "walk", 0, 9, 1.0)];
+        let predictions = [
+            segment("a.mp4", "walk", 0, 9, 0.9),
+            segment("a.mp4", "walk", 0, 9, 0.8),
+        ];
+        assert_eq!(
+            label_average_precision(&truth, &predictions, "walk", 0.5),
+            1.0
+        );
+        let predictions = [
+            segment("a.mp4", "walk", 0, 9, 0.8),
+            segment("b.mp4", "walk", 0, 9, 0.9),
+        ];
+        assert_eq!(
+            label_average_precision(&truth, &predictions, "walk", 0.5),
+            0.5
+        );
+    }
+
+    #[test]
+    fn counts_frames() {
+        let videos = BTreeSet::from(["a.mp4".to_string()]);
+        let labels = ["run".to_string(), "walk".to_string()];
+        let truth = [segment("a.mp4", "walk", 0, 9, 1.0)];
+        let predictions = [
+            segment("a.mp4", "walk", 5, 14, 1.0),
+            segment("a.mp4", "run", 20, 21, 1.0),
+            segment("b.mp4", "run", 0, 99, 1.0),
+        ];
+        let (metrics, confusion) = frame_metrics(&videos, &labels, &truth, &predictions);
+        let counts = |metrics: &LabelMetrics| {
+            (
+                metrics.true_positives,
+                metrics.false_positives,
+                metrics.false_negatives,
+            )
+        };
+        assert_eq!(counts(&metrics[0]), (0, 2, 0));
+        assert_eq!(counts(&metrics[1]), (5, 5, 5));
+        assert_eq!(metrics[1].precision, 0.5);
+        assert_eq!(metrics[1].recall, 0.5);
+        assert_eq!(metrics[1].f1, 0.5);
+        assert_eq!(metrics[0].f1, 0.0);
+        assert_eq!(confusion, [[0, 0, 0], [0, 5, 5], [2, 5, 0]]);
+    }
+
+    #[test]
+    fn counts_overlapping_labels_once_per_frame() {
+        let videos = BTreeSet::from(["a.mp4".to_string()]);
+        let labels = ["walk".to_string()];
+        let truth = [
+            segment("a.mp4", "walk", 0, 9, 1.0),
+            segment("a.mp4", "walk", 5, 14, 1.0),
+        ];
+        let (metrics, _) = frame_metrics(&videos, &labels, &truth, &truth);
+        assert_eq!(metrics[0].true_positives, 15);
+        assert_eq!(metrics[0].f1, 1.0);
+    }
+
+    #[test]
+    fn counts_frames_of_huge_segments() {
+        let videos = BTreeSet::from(["a.mp4".to_string()]);
+        let labels = ["walk".to_string()];
+        let truth = [segment("a.mp4", "walk", 0, 9, 1.0)];
+        let predictions = [segment("a.mp4", "walk", 0, u32::MAX, 1.0)];
+        let (metrics, confusion) = frame_metrics(&videos, &labels, &truth, &predictions);
+        assert_eq!(metrics[0].true_positives, 10);
+        assert_eq!(metrics[0].false_positives, u32::MAX - 9);
+        assert_eq!(confusion[1][0], u32::MAX - 9);
+    }
+
+    #[test]
+    fn evaluates_against_one_annotator() {
+        let annotation = |start_frame, end_frame, author: &str| FrameAnnotation {
+            author: Some(author.to_string()),
+            ..FrameAnnotation::new(start_frame, end_frame, "walk".to_string())
+        };
+        let truth = HashMap::from([(
+            "a.mp4".to_string(),
+            vec![
+                annotation(0, 9, "ana"),
+                annotation(20, 29, "ben"),
+                FrameAnnotation::new(40, 49, String::new()),
+            ],
+        )]);
+        let predictions = [segment("a.mp4", "walk", 0, 9, 1.0)];
+
+        let report = evaluate(&truth, None, &predictions, &[0.5]);
+        assert_eq!(report.merged_annotators, 2);
+        assert_eq!(report.labels[0].recall, 0.5);
+        assert_eq!(report.mean_average_precision, [0.5]);
+
+        let report = evaluate(&truth, Some("ana"), &predictions, &[0.5]);
+        assert_eq!(report.annotator.as_deref(), Some("ana"));
+        assert_eq!(report.merged_annotators, 1);
+        assert_eq!(report.labels[0].recall, 1.0);
+        assert_eq!(report.mean_average_precision, [1.0]);
+        assert!(report.worst_errors.is_empty());
+    }
+
+    #[test]
+    fn parses_thresholds() {
+        assert_eq!(parse_thresholds("0.3, 0.5,1"), Some(vec![0.3, 0.5, 1.0]));
+        assert_eq!(parse_thresholds("0.5,0"), None);
+        assert_eq!(parse_thresholds("1.5"), None);
+        assert_eq!(parse_thresholds("half"), None);
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use opencv::{
+    core::{self, MatTraitConst, MatTraitConstManual},
+    imgproc,
+    videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
+};
+
+use crate::{
+    app::FrameAnnotation,
+    project::Video,
+    utils::{fingerprint, load_video},
+};
+
+/// Width of the thumbnails in the video library.
+pub const THUMBNAIL_WIDTH: i32 = 96;
+
+/// Properties of a video as reported by OpenCV.
+#[derive(Clone)]
+pub struct VideoInfo {
+    pub frame_count: u32,
+    pub fps: f64,
+    pub width: u32,
+    pub height: u32,
+    pub codec: String,
+    pub thumbnail: Option<egui::TextureHandle>,
+}
+
+impl VideoInfo {
+    /// Duration in seconds, 0 if the video doesn't report its frame rate.
+    pub fn duration(&self) -> f64 {
+        if self.fps > 0.0 {
+            self.frame_count as f64 / self.fps
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Video metadata and thumbnails, read in the background since opening every video of
+/// a large project takes a while.
+#[derive(Default)]
+pub struct VideoLibrary {
+    infos: Arc<Mutex<HashMap<Video, VideoInfo>>>,
+    requested: HashSet<Video>,
+}
+
+impl VideoLibrary {
+    pub fn info(&self, video: &str) -> Option<VideoInfo> {
+        self.infos.lock().unwrap().get(video).cloned()
+    }
+
+    /// Starts reading the videos that haven't been requested before.
+    pub fn request(&mut self, videos: &[(Video, PathBuf)], ctx: &egui::Context) {
+        let pending = videos
+            .iter()
+            .filter(|(video, _)| self.requested.insert(video.clone()))
+            .cloned()
+            .collect::<Vec<_>>();
+        if pending.is_empty() {
+            return;
+        }
+        let infos = self.infos.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            for (video, path) in pending {
+                let info = read_info(&video, &path, &ctx);
+                infos.lock().unwrap().insert(video, info);
+                ctx.request_repaint();
+            }
+        });
+    }
+}
+
+/// Fingerprints of new videos, computed in the background since reading the start and
+/// the end of every new video takes a while on slow drives.
+#[derive(Default)]
+pub struct Fingerprinter {
+    finished: Arc<Mutex<Option<HashMap<String, Video>>>>,
+    running: bool,
+}
+
+impl Fingerprinter {
+    /// Starts fingerprinting `videos`, the result is taken with
+    /// [`Fingerprinter::take_finished`] once all of them are done.
+    pub fn request(&mut self, videos: Vec<(Video, PathBuf)>, ctx: &egui::Context) {
+        if self.running {
+            return;
+        }
+        self.running = true;
+        let finished = self.finished.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let mut fingerprints = HashMap::new();
+            for (video, path) in videos {
+                match fingerprint(&path) {
+                    Ok(fingerprint) => {
+                        fingerprints.insert(fingerprint, video);
+                    }
+                    Err(e) => eprintln!("Failed to fingerprint {}: {}", path.display(), e),
+                }
+            }
+            *finished.lock().unwrap() = Some(fingerprints);
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Fingerprints of the requested videos as `fingerprint -> video`, once they are all
+    /// done.
+    pub fn take_finished(&mut self) -> Option<HashMap<String, Video>> {
+        let finished = self.finished.lock().unwrap().take();
+        if finished.is_some() {
+            self.running = false;
+        }
+        finished
+    }
+}
+
+fn read_info(video: &str, path: &PathBuf, ctx: &egui::Context) -> VideoInfo {
+    let mut capture = load_video(path);
+    let fourcc = capture.get(videoio::CAP_PROP_FOURCC).unwrap_or(0.0) as u32;
+    let codec = fourcc
+        .to_le_bytes()
+        .iter()
+        .filter(|byte| byte.is_ascii_graphic())
+        .map(|byte| *byte as char)
+        .collect::<String>();
+    let frame_count = capture.get(videoio::CAP_PROP_FRAME_COUNT).unwrap_or(0.0) as u32;
+    let mut info = VideoInfo {
+        frame_count,
+        fps: capture.get(videoio::CAP_PROP_FPS).unwrap_or(0.0),
+        width: capture.get(videoio::CAP_PROP_FRAME_WIDTH).unwrap_or(0.0) as u32,
+        height: capture.get(videoio::CAP_PROP_FRAME_HEIGHT).unwrap_or(0.0) as u32,
+        codec,
+        thumbnail: None,
+    };
+
+    // the first frames are often black, so the thumbnail is taken a tenth into the video
+    let _ = capture.set(videoio::CAP_PROP_POS_FRAMES, (frame_count / 10) as f64);
+    let mut frame = core::Mat::default();
+    if !capture.read(&mut frame).unwrap_or(false) || frame.empty() {
+        return info;
+    }
+    info.thumbnail = thumbnail(format!("thumbnail_{}", video), &frame, ctx);
+    info
+}
+
+/// `frame` scaled down to [`THUMBNAIL_WIDTH`] and uploaded as texture `name`.
+pub fn thumbnail(
+    name: String,
+    frame: &core::Mat,
+    ctx: &egui::Context,
+) -> Option<egui::TextureHandle> {
+    let size = frame.size().ok()?;
+    let height = (size.height * THUMBNAIL_WIDTH / size.width.max(1)).max(1);
+    let mut resized = core::Mat::default();
+    let mut rgb = core::Mat::default();
+    imgproc::resize(
+        frame,
+        &mut resized,
+        core::Size::new(THUMBNAIL_WIDTH, height),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )
+    .and_then(|_| imgproc::cvt_color(&resized, &mut rgb, imgproc::COLOR_BGR2RGB, 0))
+    .ok()?;
+    let size = rgb.size().ok()?;
+    Some(ctx.load_texture(
+        name,
+        egui::ColorImage::from_rgb(
+            [size.width as usize, size.height as usize],
+            rgb.data_bytes().ok()?,
+        ),
+        egui::TextureOptions::default(),
+    ))
+}
+
+/// Number of frames up to `frame_count` covered by at least one labeled annotation.
+pub fn labeled_frames(annotations: &[FrameAnnotation], frame_count: u32) -> u32 {
+    let mut ranges = annotations
+        .iter()
+        .filter(|annotation| annotation.is_labeled())
+        .map(|annotation| {
+            (
+                annotation.start_frame,
+                annotation.end_frame.min(frame_count),
+            )
+        })
+        .collect::<Vec<_>>();
+    ranges.sort();
+    let mut covered = 0;
+    let mut next_uncovered = 0;
+    for (start, end) in ranges {
+        let start = start.max(next_uncovered);
+        if end >= start {
+            covered += end - start + 1;
+            next_uncovered = end.saturating_add(1);
+        }
+    }
+    covered.min(frame_count)
+}
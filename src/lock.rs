@@ -0,0 +1,203 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::now;
+
+/// Seconds after which a lock whose heartbeat was not refreshed may be taken over.
+pub const STALE_AFTER: u64 = 120;
+pub const HEARTBEAT_INTERVAL: u64 = 30;
+
+/// Advisory lock stored next to `project.json` while someone has the project open.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectLock {
+    pub holder: String,
+    pub host: String,
+    pub pid: u32,
+    pub heartbeat: u64,
+}
+
+pub enum LockState {
+    Acquired,
+    HeldByOther(ProjectLock),
+    Stale(ProjectLock),
+    /// The lock file can't be written, e.g. on a read-only share.
+    Failed(io::Error),
+}
+
+impl ProjectLock {
+    fn new(holder: &str) -> Self {
+        Self {
+            holder: holder.to_string(),
+            host: hostname(),
+            pid: std::process::id(),
+            heartbeat: now(),
+        }
+    }
+
+    pub fn is_ours(&self) -> bool {
+        self.host == hostname() && self.pid == std::process::id()
+    }
+
+    pub fn is_stale(&self) -> bool {
+        now().saturating_sub(self.heartbeat) > STALE_AFTER
+    }
+}
+
+pub fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| {
+            fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or("unknown".to_string())
+}
+
+fn lock_path(root: &Path) -> PathBuf {
+    root.join("project.lock")
+}
+
+pub fn read_lock(root: &Path) -> Option<ProjectLock> {
+    let content = fs::read_to_string(lock_path(root)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_lock(root: &Path, lock: &ProjectLock) -> std::io::Result<()> {
+    fs::write(lock_path(root), serde_json::to_string_pretty(lock)?)
+}
+
+/// Writes our lock only if there is no lock file yet, so that of two processes opening
+/// the project at the same time only one gets it.
+fn create_lock(root: &Path, lock: &ProjectLock) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(lock_path(root))?;
+    file.write_all(serde_json::to_string_pretty(lock)?.as_bytes())?;
+    file.sync_all()
+}
+
+/// Takes the lock unless another live process holds it.
+pub fn try_acquire(root: &Path, holder: &str) -> LockState {
+    match create_lock(root, &ProjectLock::new(holder)) {
+        Ok(()) => return LockState::Acquired,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return LockState::Failed(e),
+    }
+    // a lock that can't be read may be being written right now
+    let lock = read_lock(root).or_else(|| {
+        thread::sleep(Duration::from_millis(100));
+        read_lock(root)
+    });
+    match lock {
+        Some(lock) if !lock.is_ours() && lock.is_stale() => LockState::Stale(lock),
+        Some(lock) if !lock.is_ours() => LockState::HeldByOther(lock),
+        _ => match acquire(root, holder) {
+            Ok(()) => LockState::Acquired,
+            Err(e) => LockState::Failed(e),
+        },
+    }
+}
+
+/// Writes our lock, replacing whatever lock is present.
+pub fn acquire(root: &Path, holder: &str) -> std::io::Result<()> {
+    write_lock(root, &ProjectLock::new(holder))
+}
+
+pub fn heartbeat(root: &Path) {
+    if let Some(mut lock) = read_lock(root) {
+        if lock.is_ours() {
+            lock.heartbeat = now();
+            if let Err(e) = write_lock(root, &lock) {
+                eprintln!("Failed to refresh lock file: {:?}", e);
+            }
+        }
+    }
+}
+
+pub fn release(root: &Path) {
+    if read_lock(root).map_or(false, |lock| lock.is_ours()) {
+        let _ = fs::remove_file(lock_path(root));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("classroom_lock_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn foreign_lock(heartbeat: u64) -> ProjectLock {
+        ProjectLock {
+            holder: "ana".to_string(),
+            host: "elsewhere".to_string(),
+            pid: 0,
+            heartbeat,
+        }
+    }
+
+    #[test]
+    fn acquires_a_free_project() {
+        let dir = project_dir("free");
+        assert!(matches!(try_acquire(&dir, "ben"), LockState::Acquired));
+        assert!(read_lock(&dir).unwrap().is_ours());
+        // taking our own lock again keeps it
+        assert!(matches!(try_acquire(&dir, "ben"), LockState::Acquired));
+        release(&dir);
+        assert!(read_lock(&dir).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn leaves_a_live_lock_alone() {
+        let dir = project_dir("live");
+        write_lock(&dir, &foreign_lock(now())).unwrap();
+        assert!(matches!(
+            try_acquire(&dir, "ben"),
+            LockState::HeldByOther(lock) if lock.holder == "ana"
+        ));
+        assert_eq!(read_lock(&dir).unwrap().holder, "ana");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_a_stale_lock() {
+        let dir = project_dir("stale");
+        write_lock(&dir, &foreign_lock(now() - STALE_AFTER - 1)).unwrap();
+        assert!(matches!(try_acquire(&dir, "ben"), LockState::Stale(_)));
+        assert_eq!(read_lock(&dir).unwrap().holder, "ana");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn takes_over_a_broken_lock() {
+        let dir = project_dir("broken");
+        fs::write(lock_path(&dir), "{").unwrap();
+        assert!(matches!(try_acquire(&dir, "ben"), LockState::Acquired));
+        assert!(read_lock(&dir).unwrap().is_ours());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_a_lock_that_cant_be_written() {
+        let dir = project_dir("unwritable").join("missing");
+        assert!(matches!(try_acquire(&dir, "ben"), LockState::Failed(_)));
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+}
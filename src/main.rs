@@ -1,9 +1,27 @@
+mod agreement;
 mod app;
+mod cli;
+mod evaluation;
+mod library;
+mod lock;
+mod merge;
+mod model;
 mod project;
+mod scores;
+mod settings;
+mod shots;
+mod similarity;
+mod storage;
+mod uncertainty;
 mod utils;
 mod views;
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     let app = app::App::new();
     let options = eframe::NativeOptions::default();
     let _ = eframe::run_native(
@@ -0,0 +1,477 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    app::FrameAnnotation,
+    project::{ProjectConfig, Video},
+    utils::new_id,
+};
+
+/// An annotation that was changed differently on both sides.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub video: Video,
+    pub base: Option<FrameAnnotation>,
+    pub ours: Option<FrameAnnotation>,
+    pub theirs: Option<FrameAnnotation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+}
+
+/// A setting both sides changed differently, merged by keeping our value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingConflict {
+    /// Name of the setting in `project.json`.
+    pub setting: &'static str,
+    /// Video or label the setting belongs to, `None` for project-wide settings.
+    pub entry: Option<String>,
+}
+
+pub struct MergeResult {
+    pub config: ProjectConfig,
+    pub conflicts: Vec<Conflict>,
+    /// Settings where our value was kept over theirs, to be checked by hand.
+    pub setting_conflicts: Vec<SettingConflict>,
+}
+
+/// Key used to correlate annotations between the three versions. Annotations written
+/// before ids existed fall back to their content.
+fn annotation_key(annotation: &FrameAnnotation) -> String {
+    if annotation.id.is_empty() {
+        format!(
+            "{}:{}:{}",
+            annotation.label, annotation.start_frame, annotation.end_frame
+        )
+    } else {
+        annotation.id.clone()
+    }
+}
+
+fn by_key(annotations: Option<&Vec<FrameAnnotation>>) -> HashMap<String, &FrameAnnotation> {
+    annotations
+        .into_iter()
+        .flatten()
+        .map(|annotation| (annotation_key(annotation), annotation))
+        .collect()
+}
+
+/// Whether both sides changed a value, each in its own way.
+fn both_changed<T: PartialEq>(base: Option<&T>, ours: Option<&T>, theirs: Option<&T>) -> bool {
+    ours != theirs && ours != base && theirs != base
+}
+
+/// Three-way merge of a single value, preferring `ours` when both sides changed it.
+fn merge_value<T: Clone + PartialEq>(
+    base: Option<&T>,
+    ours: Option<&T>,
+    theirs: Option<&T>,
+) -> Option<T> {
+    if ours == theirs || theirs == base {
+        ours.cloned()
+    } else if ours == base {
+        theirs.cloned()
+    } else {
+        ours.cloned()
+    }
+}
+
+pub fn merge(base: &ProjectConfig, ours: &ProjectConfig, theirs: &ProjectConfig) -> MergeResult {
+    let mut conflicts = Vec::new();
+
+    let videos = ours
+        .annotations
+        .keys()
+        .chain(theirs.annotations.keys())
+        .cloned()
+        .collect::<HashSet<_>>();
+
+    let mut annotations = HashMap::new();
+    for video in videos {
+        let base_annotations = by_key(base.annotations.get(&video));
+        let our_annotations = by_key(ours.annotations.get(&video));
+        let their_annotations = by_key(theirs.annotations.get(&video));
+
+        // keep our order and append annotations only known to them
+        let mut keys = Vec::new();
+        let mut seen = HashSet::new();
+        for annotation in ours
+            .annotations
+            .get(&video)
+            .into_iter()
+            .chain(theirs.annotations.get(&video))
+            .flatten()
+        {
+            let key = annotation_key(annotation);
+            if seen.insert(key.clone()) {
+                keys.push(key);
+            }
+        }
+
+        let mut merged = Vec::new();
+        for key in keys {
+            let base_annotation = base_annotations.get(&key).copied();
+            let our_annotation = our_annotations.get(&key).copied();
+            let their_annotation = their_annotations.get(&key).copied();
+
+            if our_annotation == their_annotation || their_annotation == base_annotation {
+                merged.extend(our_annotation.cloned());
+            } else if our_annotation == base_annotation {
+                merged.extend(their_annotation.cloned());
+            } else {
+                conflicts.push(Conflict {
+                    video: video.clone(),
+                    base: base_annotation.cloned(),
+                    ours: our_annotation.cloned(),
+                    theirs: their_annotation.cloned(),
+                });
+            }
+        }
+        annotations.insert(video, merged);
+    }
+    // videos both sides removed stay as empty lists, which deletes them from the
+    // annotation store when the merged project is written
+    for video in base.annotations.keys() {
+        annotations.entry(video.clone()).or_default();
+    }
+
+    // a label stays unless one side removed it
+    let used_labels = ours
+        .used_labels
+        .iter()
+        .chain(
+            theirs
+                .used_labels
+                .iter()
+                .filter(|label| !ours.used_labels.contains(label)),
+        )
+        .filter(|label| {
+            !base.used_labels.contains(label)
+                || (ours.used_labels.contains(label) && theirs.used_labels.contains(label))
+        })
+        .cloned()
+        .collect();
+
+    let union = |ours: &Vec<String>, theirs: &Vec<String>| {
+        let mut union = ours.clone();
+        union.extend(theirs.iter().filter(|item| !ours.contains(item)).cloned());
+        union
+    };
+
+    let mut setting_conflicts = Vec::new();
+    let label_attributes = merge_map(
+        "label_attributes",
+        (
+            &base.label_attributes,
+            &ours.label_attributes,
+            &theirs.label_attributes,
+        ),
+        &mut setting_conflicts,
+    );
+    let assignments = merge_map(
+        "assignments",
+        (&base.assignments, &ours.assignments, &theirs.assignments),
+        &mut setting_conflicts,
+    );
+    let video_status = merge_map(
+        "video_status",
+        (&base.video_status, &ours.video_status, &theirs.video_status),
+        &mut setting_conflicts,
+    );
+    let sessions = merge_map(
+        "sessions",
+        (&base.sessions, &ours.sessions, &theirs.sessions),
+        &mut setting_conflicts,
+    );
+    let fingerprints = merge_map(
+        "fingerprints",
+        (&base.fingerprints, &ours.fingerprints, &theirs.fingerprints),
+        &mut setting_conflicts,
+    );
+    let shot_cuts = merge_map(
+        "shot_cuts",
+        (&base.shot_cuts, &ours.shot_cuts, &theirs.shot_cuts),
+        &mut setting_conflicts,
+    );
+    let proposals = merge_map(
+        "proposals",
+        (&base.proposals, &ours.proposals, &theirs.proposals),
+        &mut setting_conflicts,
+    );
+    // summaries follow the annotations and are written again on the next save
+    let annotation_summaries = merge_map(
+        "annotation_summaries",
+        (
+            &base.annotation_summaries,
+            &ours.annotation_summaries,
+            &theirs.annotation_summaries,
+        ),
+        &mut Vec::new(),
+    );
+    if both_changed(
+        base.model.as_ref(),
+        ours.model.as_ref(),
+        theirs.model.as_ref(),
+    ) {
+        setting_conflicts.push(SettingConflict {
+            setting: "model",
+            entry: None,
+        });
+    }
+
+    MergeResult {
+        config: ProjectConfig {
+            video_folder: ours.video_folder.clone(),
+            labels_folder: ours.labels_folder.clone(),
+            used_labels,
+            annotations,
+            label_attributes,
+            assignments,
+            video_status,
+            sessions,
+            storage: ours.storage,
+            video_extensions: ours.video_extensions.clone(),
+            video_roots: union(&ours.video_roots, &theirs.video_roots),
+            external_videos: union(&ours.external_videos, &theirs.external_videos),
+            fingerprints,
+            shot_cuts,
+            model: merge_value(
+                base.model.as_ref(),
+                ours.model.as_ref(),
+                theirs.model.as_ref(),
+            ),
+            proposals,
+            annotation_summaries,
+        },
+        conflicts,
+        setting_conflicts,
+    }
+}
+
+/// Merges the per-video or per-label values of `setting` given as `(base, ours, theirs)`,
+/// adding the entries whose value both sides changed to `conflicts`.
+fn merge_map<T: Clone + PartialEq>(
+    setting: &'static str,
+    (base, ours, theirs): (
+        &HashMap<String, T>,
+        &HashMap<String, T>,
+        &HashMap<String, T>,
+    ),
+    conflicts: &mut Vec<SettingConflict>,
+) -> HashMap<String, T> {
+    let mut entries = ours
+        .keys()
+        .chain(theirs.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    entries.sort();
+    let mut merged = HashMap::new();
+    for entry in entries {
+        let (base, ours, theirs) = (base.get(entry), ours.get(entry), theirs.get(entry));
+        if both_changed(base, ours, theirs) {
+            conflicts.push(SettingConflict {
+                setting,
+                entry: Some(entry.clone()),
+            });
+        }
+        if let Some(value) = merge_value(base, ours, theirs) {
+            merged.insert(entry.clone(), value);
+        }
+    }
+    merged
+}
+
+impl SettingConflict {
+    pub fn describe(&self) -> String {
+        match &self.entry {
+            Some(entry) => format!("{} of {}", self.setting, entry),
+            None => self.setting.to_string(),
+        }
+    }
+}
+
+/// Applies the chosen side of a conflict to the merged configuration.
+pub fn resolve(config: &mut ProjectConfig, conflict: &Conflict, resolution: Resolution) {
+    let annotations = config
+        .annotations
+        .entry(conflict.video.clone())
+        .or_default();
+    match resolution {
+        Resolution::Ours => annotations.extend(conflict.ours.clone()),
+        Resolution::Theirs => annotations.extend(conflict.theirs.clone()),
+        Resolution::Both => {
+            annotations.extend(conflict.ours.clone());
+            if let Some(mut theirs) = conflict.theirs.clone() {
+                if conflict.ours.is_some() {
+                    theirs.id = new_id();
+                }
+                annotations.push(theirs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        model::ModelConfig,
+        project::{
+            read_config, write_config, AttributeDefinition, AttributeKind, Project, StorageLayout,
+        },
+    };
+
+    #[test]
+    fn keeps_unchanged_values() {
+        assert_eq!(merge_value(Some(&1), Some(&1), Some(&1)), Some(1));
+        assert_eq!(merge_value::<i32>(None, None, None), None);
+    }
+
+    #[test]
+    fn takes_the_changed_side() {
+        assert_eq!(merge_value(Some(&1), Some(&2), Some(&1)), Some(2));
+        assert_eq!(merge_value(Some(&1), Some(&1), Some(&3)), Some(3));
+        assert_eq!(merge_value(None, None, Some(&3)), Some(3));
+        assert_eq!(merge_value(Some(&1), Some(&1), None), None);
+        assert_eq!(merge_value(Some(&1), None, Some(&1)), None);
+    }
+
+    #[test]
+    fn takes_the_same_change_once() {
+        assert_eq!(merge_value(Some(&1), Some(&2), Some(&2)), Some(2));
+        assert_eq!(merge_value(None, Some(&2), Some(&2)), Some(2));
+    }
+
+    #[test]
+    fn reports_settings_both_sides_changed() {
+        let mut base = Project::with_root(PathBuf::from("base")).config();
+        base.shot_cuts.insert("a.mp4".to_string(), vec![10]);
+        base.shot_cuts.insert("b.mp4".to_string(), vec![10]);
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+        ours.shot_cuts.insert("a.mp4".to_string(), vec![20]);
+        theirs.shot_cuts.insert("a.mp4".to_string(), vec![30]);
+        ours.shot_cuts.insert("b.mp4".to_string(), vec![20]);
+        theirs.shot_cuts.insert("b.mp4".to_string(), vec![20]);
+        theirs.shot_cuts.insert("c.mp4".to_string(), vec![5]);
+        ours.model = Some(ModelConfig {
+            path: "ours.onnx".to_string(),
+            ..ModelConfig::default()
+        });
+        theirs.model = Some(ModelConfig {
+            path: "theirs.onnx".to_string(),
+            ..ModelConfig::default()
+        });
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(
+            result.setting_conflicts,
+            [
+                SettingConflict {
+                    setting: "shot_cuts",
+                    entry: Some("a.mp4".to_string()),
+                },
+                SettingConflict {
+                    setting: "model",
+                    entry: None,
+                },
+            ]
+        );
+        assert_eq!(result.config.shot_cuts["a.mp4"], [20]);
+        assert_eq!(result.config.shot_cuts["b.mp4"], [20]);
+        assert_eq!(result.config.shot_cuts["c.mp4"], [5]);
+        assert_eq!(result.config.model.unwrap().path, "ours.onnx");
+    }
+
+    #[test]
+    fn merges_their_model() {
+        let base = Project::with_root(PathBuf::from("base")).config();
+        let mut theirs = base.clone();
+        theirs.model = Some(ModelConfig::default());
+        let result = merge(&base, &base, &theirs);
+        assert!(result.config.model.is_some());
+        assert!(result.setting_conflicts.is_empty());
+    }
+
+    #[test]
+    fn merges_label_attributes_per_label() {
+        let definition = |name: &str| AttributeDefinition {
+            name: name.to_string(),
+            kind: AttributeKind::Bool,
+        };
+        let mut base = Project::with_root(PathBuf::from("base")).config();
+        base.label_attributes
+            .insert("walk".to_string(), vec![definition("fast")]);
+        base.label_attributes
+            .insert("run".to_string(), vec![definition("fast")]);
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+        theirs
+            .label_attributes
+            .insert("walk".to_string(), vec![definition("slow")]);
+        ours.label_attributes
+            .insert("run".to_string(), vec![definition("long")]);
+        theirs
+            .label_attributes
+            .insert("run".to_string(), vec![definition("short")]);
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(result.config.label_attributes["walk"], [definition("slow")]);
+        assert_eq!(result.config.label_attributes["run"], [definition("long")]);
+        assert_eq!(
+            result.setting_conflicts,
+            [SettingConflict {
+                setting: "label_attributes",
+                entry: Some("run".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_labels_removed_on_one_side_removed() {
+        let mut base = Project::with_root(PathBuf::from("base")).config();
+        base.used_labels = vec!["walk".to_string(), "run".to_string()];
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+        ours.used_labels = vec!["walk".to_string()];
+        theirs.used_labels.push("jump".to_string());
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(result.config.used_labels, ["walk", "jump"]);
+    }
+
+    #[test]
+    fn deletes_videos_both_sides_removed_from_the_store() {
+        let dir =
+            std::env::temp_dir().join(format!("classroom_merge_removed_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut base = Project::with_root(dir.clone()).config();
+        base.storage = StorageLayout::PerVideo;
+        base.annotations.insert(
+            "a.mp4".to_string(),
+            vec![FrameAnnotation::new(0, 9, "walk".to_string())],
+        );
+        write_config(&dir, &base).unwrap();
+        assert!(read_config(&dir).unwrap().annotations.contains_key("a.mp4"));
+
+        let mut removed = base.clone();
+        removed.annotations.clear();
+        let result = merge(&base, &removed, &removed);
+        write_config(&dir, &result.config).unwrap();
+        assert!(!read_config(&dir).unwrap().annotations.contains_key("a.mp4"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prefers_ours_when_both_changed() {
+        assert_eq!(merge_value(Some(&1), Some(&2), Some(&3)), Some(2));
+        assert_eq!(merge_value(Some(&1), None, Some(&3)), None);
+    }
+}
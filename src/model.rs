@@ -0,0 +1,389 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use opencv::{
+    core::{self, MatTraitConst, MatTraitConstManual},
+    dnn::{self, NetTrait},
+    videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{app::FrameAnnotation, project::Video, scores::FrameScores, utils::load_video};
+
+/// Frame classification model used to propose segments, run on the CPU through OpenCV's
+/// DNN module.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelConfig {
+    /// ONNX file, relative to the project folder or starting with a path variable.
+    pub path: String,
+    pub input_width: u32,
+    pub input_height: u32,
+    /// Subtracted from the pixel values before scaling, in the channel order fed to the model.
+    pub mean: [f64; 3],
+    pub scale: f64,
+    /// Feeds RGB instead of OpenCV's BGR.
+    pub swap_rb: bool,
+    /// Applies a softmax to the outputs, for models that return logits.
+    pub softmax: bool,
+    /// Label of each output, empty for outputs that aren't proposed such as a background class.
+    pub class_names: Vec<String>,
+    /// Only every `frame_step`-th frame is classified.
+    pub frame_step: u32,
+    /// Number of classified frames the majority vote that smooths predictions runs over.
+    pub smoothing: u32,
+    /// Predictions below this score count as no class.
+    pub min_confidence: f32,
+    /// Shorter proposals are dropped.
+    pub min_segment_length: u32,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        ModelConfig {
+            path: String::new(),
+            input_width: 224,
+            input_height: 224,
+            mean: [0.0; 3],
+            scale: 1.0 / 255.0,
+            swap_rb: true,
+            softmax: true,
+            class_names: Vec::new(),
+            frame_step: 1,
+            smoothing: 5,
+            min_confidence: 0.5,
+            min_segment_length: 10,
+        }
+    }
+}
+
+impl ModelConfig {
+    fn label(&self, class: usize) -> Option<String> {
+        match self.class_names.get(class) {
+            Some(name) if name.is_empty() => None,
+            Some(name) => Some(name.clone()),
+            None => Some(format!("class_{}", class)),
+        }
+    }
+
+    /// Column name of output `class` in score files, also for outputs that aren't proposed.
+    fn score_name(&self, class: usize) -> String {
+        self.label(class)
+            .unwrap_or_else(|| format!("class_{}", class))
+    }
+}
+
+/// Best class of a classified frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Prediction {
+    pub frame: u32,
+    pub class: Option<usize>,
+    pub confidence: f32,
+}
+
+/// Pre-labeling running in the background, one video at a time.
+#[derive(Default)]
+pub struct Prelabeler {
+    shared: Arc<Mutex<Run>>,
+}
+
+#[derive(Default)]
+struct Run {
+    /// Video being classified and the share of its frames done.
+    current: Option<(Video, f32)>,
+    finished: Vec<(Video, Vec<FrameAnnotation>, FrameScores)>,
+    error: Option<(Video, String)>,
+}
+
+impl Prelabeler {
+    /// Starts classifying `video` unless another video is being classified.
+    pub fn start(
+        &mut self,
+        video: &str,
+        video_path: PathBuf,
+        model_path: PathBuf,
+        config: ModelConfig,
+        ctx: &egui::Context,
+    ) {
+        {
+            let mut run = self.shared.lock().unwrap();
+            if run.current.is_some() {
+                return;
+            }
+            run.current = Some((video.to_string(), 0.0));
+            run.error = None;
+        }
+        let shared = self.shared.clone();
+        let video = video.to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let result = propose(&video_path, &model_path, &config, |progress| {
+                shared.lock().unwrap().current = Some((video.clone(), progress));
+                ctx.request_repaint();
+            });
+            let mut run = shared.lock().unwrap();
+            run.current = None;
+            match result {
+                Ok((proposals, scores)) => run.finished.push((video, proposals, scores)),
+                Err(e) => run.error = Some((video, e)),
+            }
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.shared.lock().unwrap().current.is_some()
+    }
+
+    /// Share of `video` classified so far if it is being classified.
+    pub fn progress(&self, video: &str) -> Option<f32> {
+        match &self.shared.lock().unwrap().current {
+            Some((current, progress)) if current == video => Some(*progress),
+            _ => None,
+        }
+    }
+
+    /// Why the last run over `video` failed.
+    pub fn error(&self, video: &str) -> Option<String> {
+        match &self.shared.lock().unwrap().error {
+            Some((failed, error)) if failed == video => Some(error.clone()),
+            _ => None,
+        }
+    }
+
+    /// Proposals and per-frame scores of the videos finished since the last call.
+    pub fn take_finished(&self) -> Vec<(Video, Vec<FrameAnnotation>, FrameScores)> {
+        std::mem::take(&mut self.shared.lock().unwrap().finished)
+    }
+}
+
+/// Classifies the frames of `video_path` and turns the predictions into proposals. Also
+/// returns the scores of all outputs at the classified frames.
+pub fn propose(
+    video_path: &PathBuf,
+    model_path: &PathBuf,
+    config: &ModelConfig,
+    mut progress: impl FnMut(f32),
+) -> Result<(Vec<FrameAnnotation>, FrameScores), String> {
+    let mut net = dnn::read_net_from_onnx(&model_path.to_string_lossy())
+        .map_err(|e| format!("Failed to load model {}: {}", model_path.display(), e))?;
+    net.set_preferable_backend(dnn::DNN_BACKEND_OPENCV)
+        .and_then(|_| net.set_preferable_target(dnn::DNN_TARGET_CPU))
+        .map_err(|e| e.to_string())?;
+
+    let mut capture = load_video(video_path);
+    let total_frames = capture.get(videoio::CAP_PROP_FRAME_COUNT).unwrap_or(0.0) as u32;
+    let step = config.frame_step.max(1);
+    let mut predictions = Vec::new();
+    let mut scores = FrameScores {
+        classes: Vec::new(),
+        frames: Vec::new(),
+        values: Vec::new(),
+    };
+    let mut frame = core::Mat::default();
+    let mut frame_number = 0;
+    loop {
+        frame_number += 1;
+        if (frame_number - 1) % step != 0 {
+            if !capture.grab().unwrap_or(false) {
+                break;
+            }
+            continue;
+        }
+        if !capture.read(&mut frame).unwrap_or(false) || frame.empty() {
+            break;
+        }
+        let outputs = classify(&mut net, &frame, config)
+            .map_err(|e| format!("Failed to classify frame {}: {}", frame_number, e))?;
+        if scores.classes.is_empty() {
+            scores.classes = (0..outputs.len()).map(|i| config.score_name(i)).collect();
+            scores.values = vec![Vec::new(); outputs.len()];
+        }
+        scores.frames.push(frame_number);
+        for (values, output) in scores.values.iter_mut().zip(&outputs) {
+            values.push(*output);
+        }
+        let (class, confidence) = outputs
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((0, 0.0));
+        predictions.push(Prediction {
+            frame: frame_number,
+            class: (confidence >= config.min_confidence).then_some(class),
+            confidence,
+        });
+        if predictions.len() % 50 == 0 {
+            progress(frame_number as f32 / total_frames.max(1) as f32);
+        }
+    }
+    let proposals = segments(
+        &smooth(&predictions, &scores, config.smoothing),
+        config,
+        total_frames.max(frame_number - 1),
+    );
+    Ok((proposals, scores))
+}
+
+/// Scores of all outputs for `frame`.
+fn classify(
+    net: &mut dnn::Net,
+    frame: &core::Mat,
+    config: &ModelConfig,
+) -> opencv::Result<Vec<f32>> {
+    let blob = dnn::blob_from_image(
+        frame,
+        config.scale,
+        core::Size::new(config.input_width as i32, config.input_height as i32),
+        core::Scalar::new(config.mean[0], config.mean[1], config.mean[2], 0.0),
+        config.swap_rb,
+        false,
+        core::CV_32F,
+    )?;
+    net.set_input(&blob, "", 1.0, core::Scalar::default())?;
+    let output = net.forward_single("")?;
+    let mut scores = output.data_typed::<f32>()?.to_vec();
+    if config.softmax {
+        let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let sum = scores.iter().map(|score| (score - max).exp()).sum::<f32>();
+        for score in scores.iter_mut() {
+            *score = (*score - max).exp() / sum;
+        }
+    }
+    Ok(scores)
+}
+
+/// Replaces each prediction's class by the most common class among the `window`
+/// predictions around it, which removes single-frame flickering. A prediction whose class
+/// changes takes the score of its new class in `scores` as its confidence.
+pub fn smooth(predictions: &[Prediction], scores: &FrameScores, window: u32) -> Vec<Prediction> {
+    let half = window as usize / 2;
+    predictions
+        .iter()
+        .enumerate()
+        .map(|(i, prediction)| {
+            let neighbours =
+                &predictions[i.saturating_sub(half)..(i + half + 1).min(predictions.len())];
+            let mut best = (prediction.class, 0);
+            for candidate in neighbours {
+                let count = neighbours
+                    .iter()
+                    .filter(|other| other.class == candidate.class)
+                    .count();
+                if count > best.1 || (count == best.1 && candidate.class == prediction.class) {
+                    best = (candidate.class, count);
+                }
+            }
+            let confidence = match best.0 {
+                Some(class) if best.0 != prediction.class => scores
+                    .at(class, prediction.frame)
+                    .unwrap_or(prediction.confidence),
+                _ => prediction.confidence,
+            };
+            Prediction {
+                class: best.0,
+                confidence,
+                ..*prediction
+            }
+        })
+        .collect()
+}
+
+/// Merges runs of predictions with the same class into segments, scored by their mean
+/// confidence.
+pub fn segments(
+    predictions: &[Prediction],
+    config: &ModelConfig,
+    total_frames: u32,
+) -> Vec<FrameAnnotation> {
+    let step = config.frame_step.max(1);
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while start < predictions.len() {
+        let class = predictions[start].class;
+        let end = predictions[start..]
+            .iter()
+            .position(|prediction| prediction.class != class)
+            .map_or(predictions.len(), |length| start + length);
+        let run = &predictions[start..end];
+        start = end;
+        let Some(label) = class.and_then(|class| config.label(class)) else {
+            continue;
+        };
+        let start_frame = run[0].frame;
+        let end_frame = (run[run.len() - 1].frame + step - 1).min(total_frames.max(start_frame));
+        if end_frame - start_frame + 1 < config.min_segment_length {
+            continue;
+        }
+        let mut annotation = FrameAnnotation::new(start_frame, end_frame, label);
+        annotation.confidence = Some(
+            run.iter()
+                .map(|prediction| prediction.confidence)
+                .sum::<f32>()
+                / run.len() as f32,
+        );
+        segments.push(annotation);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoothing_takes_the_score_of_the_new_class() {
+        let scores = FrameScores {
+            classes: vec!["walk".to_string(), "run".to_string()],
+            frames: vec![1, 2, 3],
+            values: vec![vec![0.9, 0.4, 0.8], vec![0.1, 0.6, 0.2]],
+        };
+        let predictions = [(0, 0.9), (1, 0.6), (0, 0.8)]
+            .iter()
+            .zip(&scores.frames)
+            .map(|((class, confidence), frame)| Prediction {
+                frame: *frame,
+                class: Some(*class),
+                confidence: *confidence,
+            })
+            .collect::<Vec<_>>();
+        let smoothed = smooth(&predictions, &scores, 3);
+        assert!(smoothed
+            .iter()
+            .all(|prediction| prediction.class == Some(0)));
+        let confidences = smoothed
+            .iter()
+            .map(|prediction| prediction.confidence)
+            .collect::<Vec<_>>();
+        assert_eq!(confidences, [0.9, 0.4, 0.8]);
+    }
+
+    #[test]
+    fn smoothing_keeps_ties() {
+        let scores = FrameScores {
+            classes: vec!["walk".to_string(), "run".to_string()],
+            frames: vec![1, 2],
+            values: vec![vec![0.9, 0.3], vec![0.1, 0.7]],
+        };
+        let predictions = [
+            Prediction {
+                frame: 1,
+                class: Some(0),
+                confidence: 0.9,
+            },
+            Prediction {
+                frame: 2,
+                class: Some(1),
+                confidence: 0.7,
+            },
+        ];
+        let smoothed = smooth(&predictions, &scores, 3);
+        assert_eq!(smoothed[0].class, Some(0));
+        assert_eq!(smoothed[1].class, Some(1));
+        assert_eq!(smoothed[1].confidence, 0.7);
+    }
+}
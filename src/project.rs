@@ -1,11 +1,27 @@
 use std::{
+    cell::{RefCell, RefMut},
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::app::FrameAnnotation;
+use crate::{
+    app::{AttributeValue, FrameAnnotation},
+    library::labeled_frames,
+    lock::ProjectLock,
+    model::ModelConfig,
+    scores::FrameScores,
+    storage::{matches_query, open_store, AnnotationStore, SqliteStore, SQLITE_FILE},
+    utils::{
+        contract_path, expand_path, image_sequence_pattern, legacy_id, serialize_sorted,
+        storage_name,
+    },
+};
 
 pub type Label = String;
 pub type Video = String;
@@ -17,6 +33,213 @@ pub struct Project {
     pub labels_folder: PathBuf,
     pub annotations: HashMap<Label, Vec<FrameAnnotation>>,
     pub used_labels: HashSet<Label>,
+    pub label_attributes: HashMap<Label, Vec<AttributeDefinition>>,
+    pub assignments: HashMap<Video, Vec<String>>,
+    pub video_status: HashMap<Video, VideoStatus>,
+    pub sessions: HashMap<Video, LabelSession>,
+    pub storage: StorageLayout,
+    /// File extensions scanned for in the video folder, without the dot.
+    pub video_extensions: Vec<String>,
+    /// Folders outside the project scanned for videos, possibly starting with a path variable.
+    pub video_roots: Vec<String>,
+    /// Single videos outside the project, possibly starting with a path variable.
+    pub external_videos: Vec<String>,
+    /// Values of `$NAME` path variables on this machine, from the user settings.
+    pub path_variables: HashMap<String, String>,
+    /// Content fingerprint of each video, used to follow videos that were renamed or moved.
+    pub fingerprints: HashMap<Video, String>,
+    /// Detected shot cuts of each video, as the first frames of the new shots.
+    pub shot_cuts: HashMap<Video, Vec<u32>>,
+    pub model: Option<ModelConfig>,
+    /// Segments proposed by the model that haven't been accepted or rejected yet.
+    pub proposals: HashMap<Video, Vec<FrameAnnotation>>,
+    /// Summaries saved with the project, standing in for annotations that aren't loaded.
+    pub annotation_summaries: HashMap<Video, AnnotationSummary>,
+    /// Videos re-linked by [`Project::relink_videos`] as `(old name, new name)`.
+    pub relinked_videos: Vec<(Video, Video)>,
+    /// Videos that have annotations but no file, with their number of annotations.
+    pub missing_videos: Vec<(Video, usize)>,
+    /// Videos whose annotations have been read from the store, unused with [`StorageLayout::SingleFile`].
+    pub loaded_videos: HashSet<Video>,
+    pub all_loaded: bool,
+    /// Videos whose annotations couldn't be read from the store, with the error. They stay
+    /// unloaded and are left out when saving so that the stored annotations survive.
+    pub unreadable_videos: HashMap<Video, String>,
+    /// Whether annotations without an id got one while loading, cleared by the next save.
+    pub filled_ids: bool,
+    /// Database edits are written to with [`StorageLayout::Sqlite`], opened on the first
+    /// edit and kept open since edits come in with every keystroke.
+    sqlite: Rc<RefCell<Option<SqliteStore>>>,
+    /// Lock of another user if the project was opened read-only.
+    pub locked_by: Option<ProjectLock>,
+    /// Why our lock couldn't be written, the project is read-only then as well.
+    pub lock_error: Option<String>,
+    /// Modification time of `project.json` when it was last loaded or saved.
+    pub loaded_modified: Option<SystemTime>,
+}
+
+pub fn default_video_extensions() -> Vec<String> {
+    ["mp4", "avi", "mov", "mkv", "webm", "m4v", "mpg"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// Where labeling of a video stopped, restored when the video is opened again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelSession {
+    pub frame: u32,
+    #[serde(default)]
+    pub start_frame: Option<u32>,
+    #[serde(default)]
+    pub end_frame: Option<u32>,
+    #[serde(default = "default_timeline_zoom")]
+    pub timeline_zoom: f32,
+}
+
+fn default_timeline_zoom() -> f32 {
+    1.0
+}
+
+/// Where annotations are stored on disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum StorageLayout {
+    /// All annotations live in `project.json`.
+    #[default]
+    SingleFile,
+    /// Each video has a `<video>.annotations.json` file in the labels folder, and
+    /// `project.json` only holds settings and the taxonomy.
+    PerVideo,
+    /// Annotations live in `annotations.sqlite` in the labels folder and every edit is
+    /// written right away.
+    Sqlite,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    ReadOnly(ProjectLock),
+    NotLocked(String),
+    ChangedOnDisk,
+    Io(std::io::Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::ReadOnly(lock) => {
+                write!(f, "project is locked by {} on {}", lock.holder, lock.host)
+            }
+            SaveError::NotLocked(e) => write!(f, "project can't be locked: {}", e),
+            SaveError::ChangedOnDisk => {
+                write!(f, "project.json was changed on disk since it was loaded")
+            }
+            SaveError::Io(e) => write!(f, "failed to write project: {}", e),
+        }
+    }
+}
+
+/// Review state of a video.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum VideoStatus {
+    #[default]
+    Unlabeled,
+    InProgress,
+    Submitted,
+    Approved,
+    Rejected {
+        comment: String,
+    },
+}
+
+impl VideoStatus {
+    /// Whether the video still needs work from its annotator.
+    pub fn is_unfinished(&self) -> bool {
+        matches!(
+            self,
+            VideoStatus::Unlabeled | VideoStatus::InProgress | VideoStatus::Rejected { .. }
+        )
+    }
+
+    pub const NAMES: [&'static str; 5] = [
+        "unlabeled",
+        "in progress",
+        "submitted",
+        "approved",
+        "rejected",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoStatus::Unlabeled => Self::NAMES[0],
+            VideoStatus::InProgress => Self::NAMES[1],
+            VideoStatus::Submitted => Self::NAMES[2],
+            VideoStatus::Approved => Self::NAMES[3],
+            VideoStatus::Rejected { .. } => Self::NAMES[4],
+        }
+    }
+}
+
+/// What the video library shows about the annotations of a video. Saved in `project.json`
+/// when annotations live in a store, so that the library doesn't read every video's
+/// annotations.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnnotationSummary {
+    /// Number of labeled annotations.
+    pub count: usize,
+    /// Frames covered by at least one labeled annotation.
+    pub labeled_frames: u32,
+    pub last_edit: Option<u64>,
+}
+
+impl AnnotationSummary {
+    pub fn of(annotations: &[FrameAnnotation]) -> Self {
+        AnnotationSummary {
+            count: annotations
+                .iter()
+                .filter(|annotation| annotation.is_labeled())
+                .count(),
+            labeled_frames: labeled_frames(annotations, u32::MAX),
+            last_edit: annotations
+                .iter()
+                .filter_map(|annotation| annotation.modified_at.or(annotation.created_at))
+                .max(),
+        }
+    }
+
+    /// Share of the frames of a video of `frame_count` frames that are labeled.
+    pub fn coverage(&self, frame_count: u32) -> f32 {
+        if frame_count == 0 {
+            return 0.0;
+        }
+        self.labeled_frames.min(frame_count) as f32 / frame_count as f32
+    }
+}
+
+/// A typed attribute that annotators can fill in for every annotation of a label.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub kind: AttributeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AttributeKind {
+    Bool,
+    Enum(Vec<String>),
+    Number,
+    Text,
+}
+
+impl AttributeDefinition {
+    pub fn default_value(&self) -> AttributeValue {
+        match &self.kind {
+            AttributeKind::Bool => AttributeValue::Bool(false),
+            AttributeKind::Enum(options) => {
+                AttributeValue::Text(options.first().cloned().unwrap_or_default())
+            }
+            AttributeKind::Number => AttributeValue::Number(0.0),
+            AttributeKind::Text => AttributeValue::Text(String::new()),
+        }
+    }
 }
 
 impl Project {
@@ -30,7 +253,472 @@ impl Project {
             labels_folder,
             annotations: HashMap::new(),
             used_labels: HashSet::new(),
+            label_attributes: HashMap::new(),
+            assignments: HashMap::new(),
+            video_status: HashMap::new(),
+            sessions: HashMap::new(),
+            storage: StorageLayout::SingleFile,
+            video_extensions: default_video_extensions(),
+            video_roots: Vec::new(),
+            external_videos: Vec::new(),
+            path_variables: HashMap::new(),
+            fingerprints: HashMap::new(),
+            shot_cuts: HashMap::new(),
+            model: None,
+            proposals: HashMap::new(),
+            annotation_summaries: HashMap::new(),
+            relinked_videos: Vec::new(),
+            missing_videos: Vec::new(),
+            loaded_videos: HashSet::new(),
+            all_loaded: false,
+            unreadable_videos: HashMap::new(),
+            filled_ids: false,
+            sqlite: Rc::default(),
+            locked_by: None,
+            lock_error: None,
+            loaded_modified: None,
+        }
+    }
+
+    /// Video sources as `(name, path)`. Videos below the video folder are named by their
+    /// path relative to it, videos below external roots by the root followed by the
+    /// relative path, and single external videos by their path as registered. The name
+    /// is the key used for annotations, assignments and status.
+    pub fn videos(&self) -> Vec<(Video, PathBuf)> {
+        let mut videos = self.scan_videos(&self.path.join(&self.video_folder), "");
+        for root in &self.video_roots {
+            let prefix = format!("{}/", root.trim_end_matches('/'));
+            videos.extend(self.scan_videos(&expand_path(root, &self.path_variables), &prefix));
+        }
+        for video in &self.external_videos {
+            let path = self.video_path(video);
+            if path.exists() {
+                videos.push((video.clone(), path));
+            }
+        }
+        videos.sort();
+        videos.dedup_by(|a, b| a.0 == b.0);
+        videos
+    }
+
+    /// Folders of numbered images count as one video.
+    fn scan_videos(&self, root: &Path, prefix: &str) -> Vec<(Video, PathBuf)> {
+        let mut videos = Vec::new();
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let path = entry.path();
+                let is_video = if path.is_dir() {
+                    let is_sequence = image_sequence_pattern(&path).is_some();
+                    if !is_sequence {
+                        dirs.push(path.clone());
+                    }
+                    is_sequence
+                } else {
+                    self.is_video_file(&path)
+                };
+                if !is_video {
+                    continue;
+                }
+                if let Some(name) = path.strip_prefix(root).ok().and_then(|name| name.to_str()) {
+                    videos.push((
+                        format!("{}{}", prefix, name.replace('\\', "/")),
+                        path.clone(),
+                    ));
+                }
+            }
         }
+        videos
+    }
+
+    fn is_video_file(&self, path: &Path) -> bool {
+        path.extension().map_or(false, |ext| {
+            self.video_extensions
+                .iter()
+                .any(|video| ext.eq_ignore_ascii_case(video.as_str()))
+        })
+    }
+
+    /// Where the video named `video` is on this machine.
+    pub fn video_path(&self, video: &str) -> PathBuf {
+        if video.starts_with('$') || Path::new(video).is_absolute() {
+            expand_path(video, &self.path_variables)
+        } else {
+            self.path.join(&self.video_folder).join(video)
+        }
+    }
+
+    /// Registers footage outside the project folder without copying it: folders become
+    /// video roots that are scanned like the video folder, files and image sequences
+    /// become single videos. Returns false if `path` is neither.
+    pub fn add_external(&mut self, path: &Path) -> bool {
+        let name = contract_path(path, &self.path_variables);
+        let list = if path.is_dir() && image_sequence_pattern(path).is_none() {
+            &mut self.video_roots
+        } else if path.is_dir() || self.is_video_file(path) {
+            &mut self.external_videos
+        } else {
+            return false;
+        };
+        if !list.contains(&name) {
+            list.push(name);
+        }
+        true
+    }
+
+    /// Videos without a fingerprint yet, the ones [`Project::relink_videos`] needs
+    /// fingerprinted. Known videos are trusted by name.
+    pub fn unfingerprinted_videos(&self) -> Vec<(Video, PathBuf)> {
+        self.videos()
+            .into_iter()
+            .filter(|(name, _)| !self.fingerprints.contains_key(name))
+            .collect()
+    }
+
+    /// Re-links the annotations of videos that were renamed or moved to their new name,
+    /// given the fingerprints of the [`Project::unfingerprinted_videos`] as
+    /// `fingerprint -> video`, and records videos that have annotations but no file.
+    pub fn relink_videos(&mut self, mut unknown: HashMap<String, Video>) {
+        self.relinked_videos.clear();
+        self.missing_videos.clear();
+        let present_names = self
+            .videos()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<HashSet<_>>();
+
+        let mut gone = self
+            .fingerprints
+            .iter()
+            .filter(|(name, _)| !present_names.contains(*name))
+            .map(|(name, fingerprint)| (name.clone(), fingerprint.clone()))
+            .collect::<Vec<_>>();
+        gone.sort();
+        for (old, fingerprint) in gone {
+            if let Some(new) = unknown.remove(&fingerprint) {
+                self.rename_video(&old, &new);
+                self.relinked_videos.push((old, new));
+            }
+        }
+        for (fingerprint, name) in unknown {
+            self.fingerprints.insert(name, fingerprint);
+        }
+
+        let mut annotated = self.annotations.keys().cloned().collect::<HashSet<_>>();
+        if let Ok(Some(store)) = self.store() {
+            annotated.extend(store.videos().unwrap_or_default());
+        }
+        for video in annotated {
+            if present_names.contains(&video) {
+                continue;
+            }
+            let _ = self.load_video_annotations(&video);
+            let count = self.annotations.get(&video).map_or(0, |a| a.len());
+            if count > 0 {
+                self.missing_videos.push((video, count));
+            }
+        }
+        self.missing_videos.sort();
+    }
+
+    /// Moves everything stored for video `old` to `new`.
+    fn rename_video(&mut self, old: &str, new: &str) {
+        if let Err(e) = self
+            .load_video_annotations(old)
+            .and_then(|_| self.load_video_annotations(new))
+        {
+            eprintln!("Not moving the labels of {} to {}: {}", old, new, e);
+            return;
+        }
+        if let Some(mut annotations) = self.annotations.remove(old) {
+            self.annotations
+                .entry(new.to_string())
+                .or_default()
+                .append(&mut annotations);
+        }
+        if self.storage != StorageLayout::SingleFile {
+            // an empty list removes the video from the store on the next save
+            self.annotations.insert(old.to_string(), Vec::new());
+        }
+        if let Some(assignees) = self.assignments.remove(old) {
+            self.assignments.insert(new.to_string(), assignees);
+        }
+        if let Some(status) = self.video_status.remove(old) {
+            self.video_status.insert(new.to_string(), status);
+        }
+        if let Some(fingerprint) = self.fingerprints.remove(old) {
+            self.fingerprints.insert(new.to_string(), fingerprint);
+        }
+        if let Some(cuts) = self.shot_cuts.remove(old) {
+            self.shot_cuts.insert(new.to_string(), cuts);
+        }
+        if let Some(proposals) = self.proposals.remove(old) {
+            self.proposals.insert(new.to_string(), proposals);
+        }
+        self.annotation_summaries.remove(old);
+        for path in [Project::scores_path, Project::model_scores_path] {
+            if path(self, old).exists() {
+                if let Err(e) = std::fs::rename(path(self, old), path(self, new)) {
+                    eprintln!("Failed to move scores of {}: {}", old, e);
+                }
+            }
+        }
+        if self.features_path(old).exists() {
+            if let Err(e) = std::fs::rename(self.features_path(old), self.features_path(new)) {
+                eprintln!("Failed to move frame features of {}: {}", old, e);
+            }
+        }
+    }
+
+    /// The first unfinished video after `current` in library order, wrapping around.
+    /// `videos` is the project's video list, which callers keep since it takes a scan of
+    /// the video folders.
+    pub fn next_unfinished(
+        &self,
+        videos: &[(Video, PathBuf)],
+        current: Option<&str>,
+    ) -> Option<(Video, PathBuf)> {
+        let start = current
+            .and_then(|current| videos.iter().position(|(video, _)| video == current))
+            .map_or(0, |index| index + 1);
+        videos
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(videos.len())
+            .find(|(video, _)| {
+                Some(video.as_str()) != current && self.status(video).is_unfinished()
+            })
+            .cloned()
+    }
+
+    /// Annotators that label `video` independently of each other.
+    pub fn assignees(&self, video: &str) -> &[String] {
+        self.assignments
+            .get(video)
+            .map_or(&[], |assignees| assignees.as_slice())
+    }
+
+    pub fn remove_annotation(&mut self, video: &str, id: &str) -> Option<FrameAnnotation> {
+        let annotations = self.annotations.get_mut(video)?;
+        let index = annotations
+            .iter()
+            .position(|annotation| annotation.id == id)?;
+        let removed = annotations.remove(index);
+        if self.unreadable_videos.contains_key(video) {
+            return Some(removed);
+        }
+        if let Some(mut store) = self.write_through_store() {
+            if let Err(e) = store.remove(video, id) {
+                eprintln!("Failed to delete annotation {}: {}", id, e);
+            }
+        }
+        Some(removed)
+    }
+
+    /// Writes an added or edited annotation right away if the storage supports it,
+    /// otherwise it is written with the next save.
+    pub fn annotation_changed(&mut self, video: &str, id: &str) {
+        if self.unreadable_videos.contains_key(video) {
+            return;
+        }
+        let Some(mut store) = self.write_through_store() else {
+            return;
+        };
+        let Some(annotation) = self
+            .annotations
+            .get(video)
+            .and_then(|annotations| annotations.iter().find(|annotation| annotation.id == id))
+        else {
+            return;
+        };
+        if let Err(e) = store.put(video, annotation) {
+            eprintln!("Failed to write annotation {}: {}", id, e);
+        }
+    }
+
+    /// Location of the model file, relative paths being below the project folder.
+    pub fn model_path(&self) -> Option<PathBuf> {
+        let model = self.model.as_ref()?;
+        Some(
+            self.path
+                .join(expand_path(&model.path, &self.path_variables)),
+        )
+    }
+
+    /// Where the imported prediction scores of `video` are kept.
+    pub fn scores_path(&self, video: &str) -> PathBuf {
+        self.path
+            .join(&self.labels_folder)
+            .join("scores")
+            .join(format!("{}.csv", storage_name(video)))
+    }
+
+    /// Where the scores computed by the project's model for `video` are kept, apart from
+    /// imported ones so that a pre-labeling run never replaces those.
+    pub fn model_scores_path(&self, video: &str) -> PathBuf {
+        self.path
+            .join(&self.labels_folder)
+            .join("scores")
+            .join(format!("{}.model.csv", storage_name(video)))
+    }
+
+    /// Where the similarity search features of `video`'s frames are cached.
+    pub fn features_path(&self, video: &str) -> PathBuf {
+        self.path
+            .join(&self.labels_folder)
+            .join("features")
+            .join(format!("{}.bin", storage_name(video)))
+    }
+
+    /// Copies the score file `source` into the project after checking that it parses.
+    pub fn import_scores(&self, video: &str, source: &Path) -> Result<FrameScores, String> {
+        let content = std::fs::read_to_string(source).map_err(|e| e.to_string())?;
+        let scores = FrameScores::parse(&content)?;
+        let path = self.scores_path(video);
+        std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, content))
+            .map_err(|e| e.to_string())?;
+        Ok(scores)
+    }
+
+    /// Moves the model proposal `id` of `video` to its annotations.
+    pub fn accept_proposal(&mut self, video: &str, id: &str, author: Option<String>) {
+        let Some(proposals) = self.proposals.get_mut(video) else {
+            return;
+        };
+        let Some(index) = proposals.iter().position(|proposal| proposal.id == id) else {
+            return;
+        };
+        let annotation = proposals.remove(index).with_author(author);
+        if proposals.is_empty() {
+            self.proposals.remove(video);
+        }
+        self.used_labels.insert(annotation.label.clone());
+        self.annotations
+            .entry(video.to_string())
+            .or_default()
+            .push(annotation);
+        self.annotation_changed(video, id);
+        if self.status(video) == VideoStatus::Unlabeled {
+            self.set_status(video, VideoStatus::InProgress);
+        }
+    }
+
+    pub fn reject_proposal(&mut self, video: &str, id: &str) {
+        if let Some(proposals) = self.proposals.get_mut(video) {
+            proposals.retain(|proposal| proposal.id != id);
+            if proposals.is_empty() {
+                self.proposals.remove(video);
+            }
+        }
+    }
+
+    fn store(&self) -> std::io::Result<Option<Box<dyn AnnotationStore>>> {
+        open_store(self.storage, &self.path.join(&self.labels_folder))
+    }
+
+    fn write_through_store(&self) -> Option<RefMut<'_, SqliteStore>> {
+        if self.storage != StorageLayout::Sqlite || self.is_read_only() {
+            return None;
+        }
+        let mut sqlite = self.sqlite.borrow_mut();
+        if sqlite.is_none() {
+            let path = self.path.join(&self.labels_folder).join(SQLITE_FILE);
+            match SqliteStore::open(&path) {
+                Ok(store) => *sqlite = Some(store),
+                Err(e) => {
+                    eprintln!("Failed to open annotation store: {}", e);
+                    return None;
+                }
+            }
+        }
+        RefMut::filter_map(sqlite, Option::as_mut).ok()
+    }
+
+    /// Annotations with `label` overlapping the inclusive frame range `frames`, using the
+    /// indexes of the store where there is one.
+    pub fn query(
+        &self,
+        label: Option<&str>,
+        frames: Option<(u32, u32)>,
+    ) -> std::io::Result<Vec<(Video, FrameAnnotation)>> {
+        if let Some(store) = self.store()? {
+            return store.query(label, frames);
+        }
+        let mut matches = self
+            .annotations
+            .iter()
+            .flat_map(|(video, annotations)| {
+                annotations
+                    .iter()
+                    .filter(|annotation| matches_query(annotation, label, frames))
+                    .map(|annotation| (video.clone(), annotation.clone()))
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| (&a.0, a.1.start_frame).cmp(&(&b.0, b.1.start_frame)));
+        Ok(matches)
+    }
+
+    /// Summary of the annotations of `video`, from the saved summary if they aren't loaded.
+    pub fn annotation_summary(&self, video: &str) -> AnnotationSummary {
+        match self.annotations.get(video) {
+            Some(annotations) => AnnotationSummary::of(annotations),
+            None => self
+                .annotation_summaries
+                .get(video)
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn status(&self, video: &str) -> VideoStatus {
+        self.video_status.get(video).cloned().unwrap_or_default()
+    }
+
+    pub fn set_status(&mut self, video: &str, status: VideoStatus) {
+        self.video_status.insert(video.to_string(), status);
+    }
+
+    /// Whether someone else holds the project or our lock couldn't be written.
+    pub fn is_read_only(&self) -> bool {
+        self.locked_by.is_some() || self.lock_error.is_some()
+    }
+
+    fn modified_on_disk(&self) -> Option<SystemTime> {
+        std::fs::metadata(self.path.join("project.json"))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Writes the project unless it is read-only or someone else changed it since it was loaded.
+    pub fn save(&mut self) -> Result<(), SaveError> {
+        let on_disk = self.modified_on_disk();
+        if on_disk.is_some() && on_disk != self.loaded_modified {
+            return Err(SaveError::ChangedOnDisk);
+        }
+        self.overwrite()
+    }
+
+    /// Writes the project even if it was changed on disk.
+    pub fn overwrite(&mut self) -> Result<(), SaveError> {
+        if let Some(lock) = &self.locked_by {
+            return Err(SaveError::ReadOnly(lock.clone()));
+        }
+        if let Some(e) = &self.lock_error {
+            return Err(SaveError::NotLocked(e.clone()));
+        }
+        let mut config = self.config();
+        config
+            .annotations
+            .retain(|video, _| !self.unreadable_videos.contains_key(video));
+        write_config(&self.path, &config).map_err(SaveError::Io)?;
+        self.loaded_modified = self.modified_on_disk();
+        self.filled_ids = false;
+        Ok(())
     }
 
     pub fn config(&self) -> ProjectConfig {
@@ -39,7 +727,134 @@ impl Project {
             labels_folder: self.labels_folder.to_str().unwrap().to_string(),
             used_labels: self.used_labels.clone().into_iter().collect(),
             annotations: self.annotations.clone(),
+            label_attributes: self.label_attributes.clone(),
+            assignments: self.assignments.clone(),
+            video_status: self.video_status.clone(),
+            sessions: self.sessions.clone(),
+            storage: self.storage,
+            video_extensions: self.video_extensions.clone(),
+            video_roots: self.video_roots.clone(),
+            external_videos: self.external_videos.clone(),
+            fingerprints: self.fingerprints.clone(),
+            shot_cuts: self.shot_cuts.clone(),
+            model: self.model.clone(),
+            proposals: self.proposals.clone(),
+            annotation_summaries: self.summaries(),
+        }
+    }
+
+    /// Summaries of all videos with annotations, empty if they are all in `project.json`.
+    fn summaries(&self) -> HashMap<Video, AnnotationSummary> {
+        if self.storage == StorageLayout::SingleFile {
+            return HashMap::new();
+        }
+        self.annotation_summaries
+            .keys()
+            .chain(self.annotations.keys())
+            .map(|video| (video.clone(), self.annotation_summary(video)))
+            .filter(|(_, summary)| *summary != AnnotationSummary::default())
+            .collect()
+    }
+
+    /// Reads the annotations of `video` from the store unless they were already loaded.
+    /// A video that failed to read isn't read again until [`Project::retry_video_annotations`].
+    pub fn load_video_annotations(&mut self, video: &str) -> Result<(), String> {
+        if let Some(e) = self.unreadable_videos.get(video) {
+            return Err(e.clone());
+        }
+        if self.storage == StorageLayout::SingleFile || self.loaded_videos.contains(video) {
+            return Ok(());
+        }
+        let loaded = self
+            .store()
+            .and_then(|store| store.map_or(Ok(Vec::new()), |store| store.load(video)));
+        let mut annotations = match loaded {
+            Ok(annotations) => annotations,
+            Err(e) => {
+                eprintln!("Failed to read annotations of {}: {}", video, e);
+                self.unreadable_videos
+                    .insert(video.to_string(), e.to_string());
+                return Err(e.to_string());
+            }
+        };
+        self.filled_ids |= fill_missing_ids(video, &mut annotations);
+        self.annotations.insert(video.to_string(), annotations);
+        self.loaded_videos.insert(video.to_string());
+        Ok(())
+    }
+
+    /// Reads the annotations of a video that failed to read again, e.g. after its file
+    /// was repaired.
+    pub fn retry_video_annotations(&mut self, video: &str) -> Result<(), String> {
+        self.unreadable_videos.remove(video);
+        self.annotations.remove(video);
+        self.load_video_annotations(video)
+    }
+
+    /// Loads the annotations of all videos, needed by views that work across the whole project.
+    pub fn load_all_annotations(&mut self) {
+        if self.storage == StorageLayout::SingleFile || self.all_loaded {
+            return;
+        }
+        let videos = self
+            .store()
+            .and_then(|store| store.map_or(Ok(Vec::new()), |store| store.videos()));
+        match videos {
+            Ok(videos) => {
+                for video in videos {
+                    // failures are kept in `unreadable_videos` and shown by the views
+                    let _ = self.load_video_annotations(&video);
+                }
+                self.all_loaded = true;
+            }
+            Err(e) => eprintln!("Failed to list annotated videos: {:?}", e),
+        }
+    }
+
+    /// Fails naming the videos whose annotations can't be read, writing the whole project
+    /// would delete them from the store.
+    fn check_readable(&self) -> std::io::Result<()> {
+        if self.unreadable_videos.is_empty() {
+            return Ok(());
+        }
+        let mut videos = self.unreadable_videos.keys().cloned().collect::<Vec<_>>();
+        videos.sort();
+        Err(std::io::Error::other(format!(
+            "the annotations of {} can't be read",
+            videos.join(", ")
+        )))
+    }
+
+    /// Configuration with the annotations of all videos, for merging other versions of the
+    /// project into. Fails while some videos can't be read, the merge would take their
+    /// annotations for deleted.
+    pub fn merge_config(&mut self) -> std::io::Result<ProjectConfig> {
+        self.load_all_annotations();
+        self.check_readable()?;
+        Ok(self.config())
+    }
+
+    /// Switches the storage layout and rewrites the project in the new layout.
+    pub fn convert_storage(&mut self, storage: StorageLayout) -> Result<(), SaveError> {
+        if storage == self.storage {
+            return Ok(());
+        }
+        self.load_all_annotations();
+        self.check_readable().map_err(SaveError::Io)?;
+        let previous = self.store().map_err(SaveError::Io)?;
+        // the database may be deleted below
+        self.sqlite.take();
+        let previous_layout = self.storage;
+        self.storage = storage;
+        self.loaded_videos = self.annotations.keys().cloned().collect();
+        if let Err(e) = self.overwrite() {
+            self.storage = previous_layout;
+            return Err(e);
+        }
+        if let Some(previous) = previous {
+            previous.delete().map_err(SaveError::Io)?;
         }
+        Ok(())
     }
 }
 
@@ -48,24 +863,245 @@ pub struct ProjectConfig {
     pub video_folder: String,
     pub labels_folder: String,
     pub used_labels: Vec<Label>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub annotations: HashMap<Video, Vec<FrameAnnotation>>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub label_attributes: HashMap<Label, Vec<AttributeDefinition>>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub assignments: HashMap<Video, Vec<String>>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub video_status: HashMap<Video, VideoStatus>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub sessions: HashMap<Video, LabelSession>,
+    #[serde(default)]
+    pub storage: StorageLayout,
+    #[serde(default = "default_video_extensions")]
+    pub video_extensions: Vec<String>,
+    #[serde(default)]
+    pub video_roots: Vec<String>,
+    #[serde(default)]
+    pub external_videos: Vec<String>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub fingerprints: HashMap<Video, String>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub shot_cuts: HashMap<Video, Vec<u32>>,
+    #[serde(default)]
+    pub model: Option<ModelConfig>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub proposals: HashMap<Video, Vec<FrameAnnotation>>,
+    #[serde(default, serialize_with = "serialize_sorted")]
+    pub annotation_summaries: HashMap<Video, AnnotationSummary>,
+}
+
+impl ProjectConfig {
+    /// Sorts labels and annotations so that saving the same data always produces the same file.
+    pub fn normalize(&mut self) {
+        self.used_labels.sort();
+        for annotations in self.annotations.values_mut() {
+            annotations.sort_by(|a, b| {
+                (a.start_frame, a.end_frame, &a.label, &a.id).cmp(&(
+                    b.start_frame,
+                    b.end_frame,
+                    &b.label,
+                    &b.id,
+                ))
+            });
+        }
+    }
 }
 
 impl Project {
-    fn from(config: ProjectConfig, root: PathBuf) -> Self {
+    pub fn from(mut config: ProjectConfig, root: PathBuf) -> Self {
+        let mut filled_ids = false;
+        for (video, annotations) in config.annotations.iter_mut() {
+            filled_ids |= fill_missing_ids(video, annotations);
+        }
+
+        // annotations that came with the config don't have to be read from the store again
+        let loaded_videos = config.annotations.keys().cloned().collect();
         Project {
             path: root,
             video_folder: PathBuf::from(config.video_folder),
             labels_folder: PathBuf::from(config.labels_folder),
             annotations: config.annotations,
             used_labels: config.used_labels.into_iter().collect(),
+            label_attributes: config.label_attributes,
+            assignments: config.assignments,
+            video_status: config.video_status,
+            sessions: config.sessions,
+            storage: config.storage,
+            video_extensions: config.video_extensions,
+            video_roots: config.video_roots,
+            external_videos: config.external_videos,
+            path_variables: HashMap::new(),
+            fingerprints: config.fingerprints,
+            shot_cuts: config.shot_cuts,
+            model: config.model,
+            proposals: config.proposals,
+            annotation_summaries: config.annotation_summaries,
+            relinked_videos: Vec::new(),
+            missing_videos: Vec::new(),
+            loaded_videos,
+            all_loaded: false,
+            unreadable_videos: HashMap::new(),
+            filled_ids,
+            sqlite: Rc::default(),
+            locked_by: None,
+            lock_error: None,
+            loaded_modified: None,
+        }
+    }
+}
+
+/// Gives the annotations of `video` saved before annotations had ids a [`legacy_id`].
+/// Returns whether any annotation was missing its id.
+pub fn fill_missing_ids(video: &str, annotations: &mut [FrameAnnotation]) -> bool {
+    let mut occurrences = HashMap::new();
+    let mut filled = false;
+    for annotation in annotations.iter_mut().filter(|a| a.id.is_empty()) {
+        let occurrence = occurrences
+            .entry((
+                annotation.label.clone(),
+                annotation.start_frame,
+                annotation.end_frame,
+            ))
+            .or_insert(0);
+        annotation.id = legacy_id(
+            video,
+            &annotation.label,
+            annotation.start_frame,
+            annotation.end_frame,
+            *occurrence,
+        );
+        *occurrence += 1;
+        filled = true;
+    }
+    filled
+}
+
+fn read_config_file(path: &Path) -> std::io::Result<ProjectConfig> {
+    let config = std::fs::read_to_string(path.join("project.json"))?;
+    Ok(serde_json::from_str(&config)?)
+}
+
+/// Reads a project including all annotations, whatever the storage layout.
+pub fn read_config(path: &Path) -> std::io::Result<ProjectConfig> {
+    let (root, mut config) = if path.is_dir() {
+        (path.to_path_buf(), read_config_file(path)?)
+    } else {
+        let root = path.parent().map(PathBuf::from).unwrap_or_default();
+        let config = std::fs::read_to_string(path)?;
+        (root, serde_json::from_str(&config)?)
+    };
+    if let Some(store) = open_store(config.storage, &root.join(&config.labels_folder))? {
+        for video in store.videos()? {
+            let annotations = store.load(&video)?;
+            config.annotations.insert(video, annotations);
         }
     }
+    // the same ids as a loaded project, so that versions of a legacy project line up
+    for (video, annotations) in config.annotations.iter_mut() {
+        fill_missing_ids(video, annotations);
+    }
+    Ok(config)
 }
 
-pub fn load_project_from_path(path: &PathBuf) -> Project {
+/// Number of previous versions kept as `project.json.bak.N`.
+const BACKUPS: usize = 3;
+
+/// Writes `project.json` atomically: the new content goes to a temporary file that is
+/// renamed over the old one after the previous versions were rotated into backups.
+///
+/// Unless the layout is [`StorageLayout::SingleFile`] the annotations of every video in
+/// `config` go to the annotation store instead, which only writes what changed.
+pub fn write_config(path: &Path, config: &ProjectConfig) -> std::io::Result<()> {
+    let mut config = config.clone();
+    config.normalize();
+
+    if let Some(mut store) = open_store(config.storage, &path.join(&config.labels_folder))? {
+        for (video, annotations) in config.annotations.drain() {
+            store.replace(&video, &annotations)?;
+        }
+    }
+    let content = serde_json::to_string_pretty(&config)?;
+
     let config_path = path.join("project.json");
-    let config = std::fs::read_to_string(config_path).unwrap();
-    let config: ProjectConfig = serde_json::from_str(&config).unwrap();
-    Project::from(config, path.to_path_buf())
+    let temp_path = path.join("project.json.tmp");
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.write_all(b"\n")?;
+    file.sync_all()?;
+    drop(file);
+
+    if config_path.exists() {
+        let backup = |i: usize| path.join(format!("project.json.bak.{}", i));
+        for i in (1..BACKUPS).rev() {
+            if backup(i).exists() {
+                std::fs::rename(backup(i), backup(i + 1))?;
+            }
+        }
+        std::fs::copy(&config_path, backup(1))?;
+    }
+    std::fs::rename(&temp_path, &config_path)
+}
+
+/// Reads `project.json` of the project at `path`, leaving annotations in a store to be
+/// loaded when needed.
+pub fn load_project_from_path(path: &PathBuf) -> std::io::Result<Project> {
+    let config = read_config_file(path)?;
+    let mut project = Project::from(config, path.to_path_buf());
+    project.loaded_modified = project.modified_on_disk();
+    Ok(project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn per_video_project(name: &str) -> Project {
+        let dir =
+            std::env::temp_dir().join(format!("classroom_project_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("labels")).unwrap();
+        let config = serde_json::from_str(
+            r#"{"video_folder": "videos", "labels_folder": "labels", "used_labels": [],
+                "annotations": {}, "storage": "PerVideo"}"#,
+        )
+        .unwrap();
+        Project::from(config, dir)
+    }
+
+    fn sidecar(project: &Project, video: &str) -> PathBuf {
+        project
+            .path
+            .join("labels")
+            .join(format!("{}.annotations.json", storage_name(video)))
+    }
+
+    #[test]
+    fn refuses_to_merge_with_unreadable_videos() {
+        let mut project = per_video_project("unreadable");
+        let annotations = vec![FrameAnnotation::new(0, 9, "walk".to_string())];
+        std::fs::write(
+            sidecar(&project, "a.mp4"),
+            serde_json::to_string(&annotations).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(sidecar(&project, "b.mp4"), "not json").unwrap();
+
+        let error = project.merge_config().unwrap_err();
+        assert!(error.to_string().contains("b.mp4"));
+        assert!(project.convert_storage(StorageLayout::SingleFile).is_err());
+        // nothing was written over the file that failed to read
+        assert_eq!(
+            std::fs::read_to_string(sidecar(&project, "b.mp4")).unwrap(),
+            "not json"
+        );
+
+        std::fs::write(sidecar(&project, "b.mp4"), "[]").unwrap();
+        project.retry_video_annotations("b.mp4").unwrap();
+        let config = project.merge_config().unwrap();
+        assert_eq!(config.annotations["a.mp4"], annotations);
+        std::fs::remove_dir_all(&project.path).unwrap();
+    }
 }
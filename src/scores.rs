@@ -0,0 +1,251 @@
+use std::path::Path;
+
+use crate::utils::{csv_field, parse_csv};
+
+/// Per-frame class scores of a video, as written by an external model.
+///
+/// Score files are CSVs with a `frame` column followed by one column per class. Rows may
+/// skip frames, a row's scores then hold until the next row. Frames are numbered like in
+/// the app, where the first frame of a video is 1; files numbering them from 0 name the
+/// column `frame_index` instead and are shifted when read.
+#[derive(Debug, Clone)]
+pub struct FrameScores {
+    pub classes: Vec<String>,
+    /// Frames of the rows in ascending order.
+    pub frames: Vec<u32>,
+    /// Scores of each class, one per row.
+    pub values: Vec<Vec<f32>>,
+}
+
+impl FrameScores {
+    pub fn parse(content: &str) -> Result<FrameScores, String> {
+        let mut records = parse_csv(content).into_iter();
+        let (_, mut classes) = records.next().ok_or("the score file is empty")?;
+        let first_frame = match classes.first().map(|column| column.to_lowercase()) {
+            Some(column) if column == "frame" => 0.0,
+            Some(column) if column == "frame_index" => 1.0,
+            _ => return Err("the first column must be `frame` or `frame_index`".to_string()),
+        };
+        classes.remove(0);
+        if classes.is_empty() {
+            return Err("the score file has no class columns".to_string());
+        }
+
+        let mut rows = Vec::new();
+        for (line, fields) in records {
+            let mut fields = fields.iter();
+            let frame = fields
+                .next()
+                .and_then(|field| field.parse::<f64>().ok())
+                .filter(|frame| *frame >= 0.0)
+                .ok_or(format!("line {}: invalid frame", line))?;
+            let scores = fields
+                .map(|field| field.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| format!("line {}: invalid score", line))?;
+            if scores.len() != classes.len() {
+                return Err(format!(
+                    "line {}: expected {} scores, found {}",
+                    line,
+                    classes.len(),
+                    scores.len()
+                ));
+            }
+            rows.push(((frame + first_frame) as u32, scores));
+        }
+        rows.sort_by_key(|(frame, _)| *frame);
+        rows.dedup_by_key(|(frame, _)| *frame);
+
+        let mut values = vec![Vec::with_capacity(rows.len()); classes.len()];
+        let mut frames = Vec::with_capacity(rows.len());
+        for (frame, scores) in rows {
+            frames.push(frame);
+            for (class, score) in scores.into_iter().enumerate() {
+                values[class].push(score);
+            }
+        }
+        Ok(FrameScores {
+            classes,
+            frames,
+            values,
+        })
+    }
+
+    /// Reads the score file at `path`, `None` if there is none.
+    pub fn read(path: &Path) -> Result<Option<FrameScores>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => FrameScores::parse(&content).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Writes the scores in the format [`FrameScores::parse`] reads.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let classes = self
+            .classes
+            .iter()
+            .map(|class| csv_field(class))
+            .collect::<Vec<_>>();
+        let mut content = format!("frame,{}\n", classes.join(","));
+        for (row, frame) in self.frames.iter().enumerate() {
+            content.push_str(&frame.to_string());
+            for values in &self.values {
+                content.push_str(&format!(",{}", values[row]));
+            }
+            content.push('\n');
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)
+    }
+
+    /// Scores of all classes in row `row`.
+    pub fn row(&self, row: usize) -> Vec<f32> {
+        self.values.iter().map(|values| values[row]).collect()
+    }
+
+    /// Score of `class` at `frame`, taken from the last row at or before it.
+    pub fn at(&self, class: usize, frame: u32) -> Option<f32> {
+        let row = self
+            .frames
+            .partition_point(|row| *row <= frame)
+            .checked_sub(1)?;
+        self.values.get(class)?.get(row).copied()
+    }
+
+    /// Inclusive frame ranges of at least `min_length` frames where `class` scores at
+    /// least `threshold`, with their mean score.
+    pub fn candidates(
+        &self,
+        class: usize,
+        threshold: f32,
+        min_length: u32,
+    ) -> Vec<(u32, u32, f32)> {
+        let Some(values) = self.values.get(class) else {
+            return Vec::new();
+        };
+        let mut candidates = Vec::new();
+        let mut row = 0;
+        while row < values.len() {
+            if values[row] < threshold {
+                row += 1;
+                continue;
+            }
+            let first = row;
+            while row < values.len() && values[row] >= threshold {
+                row += 1;
+            }
+            let start = self.frames[first];
+            // the run lasts until the next row, or just its own frame at the end of the file
+            let end = self
+                .frames
+                .get(row)
+                .map_or(self.frames[row - 1], |next| next - 1);
+            let mean = values[first..row].iter().sum::<f32>() / (row - first) as f32;
+            if end - start + 1 >= min_length {
+                candidates.push((start, end, mean));
+            }
+        }
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(frames: &[u32], values: &[f32]) -> FrameScores {
+        FrameScores {
+            classes: vec!["walk".to_string()],
+            frames: frames.to_vec(),
+            values: vec![values.to_vec()],
+        }
+    }
+
+    #[test]
+    fn candidates_last_until_the_next_row() {
+        let scores = scores(&[0, 10, 20, 30], &[0.9, 0.8, 0.1, 0.9]);
+        let candidates = scores.candidates(0, 0.5, 1);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!((candidates[0].0, candidates[0].1), (0, 19));
+        assert!((candidates[0].2 - 0.85).abs() < 1e-6);
+        // the last row only covers its own frame
+        assert_eq!(candidates[1], (30, 30, 0.9));
+    }
+
+    #[test]
+    fn candidates_need_the_minimum_length() {
+        let scores = scores(&[0, 10, 20, 30], &[0.9, 0.8, 0.1, 0.9]);
+        let candidates = scores.candidates(0, 0.5, 20);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!((candidates[0].0, candidates[0].1), (0, 19));
+        assert!(scores.candidates(0, 0.5, 21).is_empty());
+    }
+
+    #[test]
+    fn candidates_include_the_threshold() {
+        let scores = scores(&[1, 2, 3], &[0.5, 0.49, 0.5]);
+        assert_eq!(scores.candidates(0, 0.5, 1), [(1, 1, 0.5), (3, 3, 0.5)]);
+    }
+
+    #[test]
+    fn candidates_of_unknown_classes() {
+        let scores = scores(&[1, 2, 3], &[0.9, 0.9, 0.9]);
+        assert!(scores.candidates(1, 0.5, 1).is_empty());
+        assert!(FrameScores::parse("frame,walk\n")
+            .unwrap()
+            .candidates(0, 0.5, 1)
+            .is_empty());
+    }
+
+    #[test]
+    fn parses_and_sorts_rows() {
+        let scores = FrameScores::parse("frame,\"walk\",run\n20,0.2,0.8\n10,0.9,0.1\n").unwrap();
+        assert_eq!(scores.classes, ["walk", "run"]);
+        assert_eq!(scores.frames, [10, 20]);
+        assert_eq!(scores.at(0, 15), Some(0.9));
+        assert_eq!(scores.at(1, 25), Some(0.8));
+        assert_eq!(scores.at(0, 5), None);
+    }
+
+    #[test]
+    fn parses_quoted_classes() {
+        let scores = FrameScores::parse("frame,\"walk, slow\",run\n1,0.2,0.8\n").unwrap();
+        assert_eq!(scores.classes, ["walk, slow", "run"]);
+        assert_eq!(scores.values, [[0.2], [0.8]]);
+    }
+
+    #[test]
+    fn shifts_frame_indices() {
+        let scores = FrameScores::parse("Frame_Index,walk\n0,0.2\n9,0.8\n").unwrap();
+        assert_eq!(scores.frames, [1, 10]);
+    }
+
+    #[test]
+    fn reads_written_scores() {
+        let scores = FrameScores {
+            classes: vec!["walk, slow".to_string(), "run".to_string()],
+            frames: vec![1, 5],
+            values: vec![vec![0.25, 0.5], vec![0.75, 0.5]],
+        };
+        let path =
+            std::env::temp_dir().join(format!("classroom_scores_{}.csv", std::process::id()));
+        scores.write(&path).unwrap();
+        let read = FrameScores::read(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.classes, scores.classes);
+        assert_eq!(read.frames, scores.frames);
+        assert_eq!(read.values, scores.values);
+    }
+
+    #[test]
+    fn rejects_invalid_score_files() {
+        assert!(FrameScores::parse("").is_err());
+        assert!(FrameScores::parse("time,walk\n").is_err());
+        assert!(FrameScores::parse("frame\n1\n").is_err());
+        assert!(FrameScores::parse("frame,walk\n1,0.5,0.5\n").is_err());
+        assert!(FrameScores::parse("frame,walk\n-1,0.5\n").is_err());
+    }
+}
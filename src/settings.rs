@@ -0,0 +1,46 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Per-user settings, stored in the home directory rather than in the project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub annotator: String,
+    /// Where `$NAME` in video paths points on this machine, e.g. `NAS` to the mount point
+    /// of the footage share.
+    #[serde(default)]
+    pub path_variables: HashMap<String, String>,
+}
+
+impl Settings {
+    fn path() -> Option<PathBuf> {
+        std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(".frameclassifier.json"))
+    }
+
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Some(path) = Self::path() {
+            if let Err(e) = fs::write(path, serde_json::to_string(self).unwrap()) {
+                eprintln!("Failed to write settings: {:?}", e);
+            }
+        }
+    }
+
+    pub fn annotator(&self) -> Option<String> {
+        let annotator = self.annotator.trim();
+        if annotator.is_empty() {
+            None
+        } else {
+            Some(annotator.to_string())
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use opencv::{
+    core::{self, MatTraitConst},
+    imgproc,
+    videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
+};
+
+use crate::{project::Video, utils::load_video};
+
+/// Bhattacharyya distance between the color histograms of two consecutive frames above
+/// which the second frame starts a new shot.
+pub const CUT_THRESHOLD: f64 = 0.45;
+
+/// Fewest frames between two cuts, so that flashes and fast motion don't produce a burst
+/// of cuts.
+pub const MIN_SHOT_LENGTH: u32 = 6;
+
+/// How many frames away from a cut S and E still snap to it.
+pub const SNAP_DISTANCE: u32 = 12;
+
+/// Width frames are scaled down to before computing their histograms.
+const ANALYSIS_WIDTH: i32 = 160;
+
+/// Shot cut detection running in the background, one video after the other.
+#[derive(Default)]
+pub struct ShotDetector {
+    shared: Arc<Mutex<Detection>>,
+}
+
+#[derive(Default)]
+struct Detection {
+    queue: VecDeque<(Video, PathBuf)>,
+    /// Video being analyzed and the share of its frames done.
+    current: Option<(Video, f32)>,
+    finished: Vec<(Video, Vec<u32>)>,
+    running: bool,
+}
+
+impl ShotDetector {
+    /// Queues the videos that aren't queued or analyzed yet and starts the worker.
+    pub fn request(&mut self, videos: &[(Video, PathBuf)], ctx: &egui::Context) {
+        let mut detection = self.shared.lock().unwrap();
+        for (video, path) in videos {
+            let queued = detection.queue.iter().any(|(queued, _)| queued == video)
+                || detection
+                    .current
+                    .as_ref()
+                    .map_or(false, |(current, _)| current == video);
+            if !queued {
+                detection.queue.push_back((video.clone(), path.clone()));
+            }
+        }
+        if detection.running || detection.queue.is_empty() {
+            return;
+        }
+        detection.running = true;
+        let shared = self.shared.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || loop {
+            let (video, path) = {
+                let mut detection = shared.lock().unwrap();
+                let Some((video, path)) = detection.queue.pop_front() else {
+                    detection.running = false;
+                    break;
+                };
+                detection.current = Some((video.clone(), 0.0));
+                (video, path)
+            };
+            let cuts = detect_cuts(&path, |progress| {
+                shared.lock().unwrap().current = Some((video.clone(), progress));
+                ctx.request_repaint();
+            });
+            let mut detection = shared.lock().unwrap();
+            detection.current = None;
+            detection.finished.push((video, cuts));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Share of `video` analyzed so far, `Some(0.0)` while it is waiting in the queue.
+    pub fn progress(&self, video: &str) -> Option<f32> {
+        let detection = self.shared.lock().unwrap();
+        match &detection.current {
+            Some((current, progress)) if current == video => Some(*progress),
+            _ => detection
+                .queue
+                .iter()
+                .any(|(queued, _)| queued == video)
+                .then_some(0.0),
+        }
+    }
+
+    /// Number of videos queued or being analyzed.
+    pub fn remaining(&self) -> usize {
+        let detection = self.shared.lock().unwrap();
+        detection.queue.len() + detection.current.iter().count()
+    }
+
+    /// Cuts of the videos finished since the last call.
+    pub fn take_finished(&self) -> Vec<(Video, Vec<u32>)> {
+        std::mem::take(&mut self.shared.lock().unwrap().finished)
+    }
+}
+
+/// Frames of `path` that start a new shot, found by comparing the hue/saturation
+/// histograms of consecutive frames.
+pub fn detect_cuts(path: &PathBuf, mut progress: impl FnMut(f32)) -> Vec<u32> {
+    let mut capture = load_video(path);
+    let total_frames = capture
+        .get(videoio::CAP_PROP_FRAME_COUNT)
+        .unwrap_or(0.0)
+        .max(1.0) as f32;
+    let mut cuts = Vec::new();
+    let mut previous: Option<core::Mat> = None;
+    let mut frame = core::Mat::default();
+    let mut frame_number = 0;
+    let mut last_cut = 0;
+    while capture.read(&mut frame).unwrap_or(false) && !frame.empty() {
+        frame_number += 1;
+        let Ok(histogram) = histogram(&frame) else {
+            continue;
+        };
+        if let Some(previous) = &previous {
+            let distance =
+                imgproc::compare_hist(previous, &histogram, imgproc::HISTCMP_BHATTACHARYYA)
+                    .unwrap_or(0.0);
+            if distance > CUT_THRESHOLD && frame_number - last_cut >= MIN_SHOT_LENGTH {
+                cuts.push(frame_number);
+                last_cut = frame_number;
+            }
+        }
+        previous = Some(histogram);
+        if frame_number % 100 == 0 {
+            progress(frame_number as f32 / total_frames);
+        }
+    }
+    cuts
+}
+
+fn histogram(frame: &core::Mat) -> opencv::Result<core::Mat> {
+    let size = frame.size()?;
+    let height = (size.height * ANALYSIS_WIDTH / size.width.max(1)).max(1);
+    let mut small = core::Mat::default();
+    imgproc::resize(
+        frame,
+        &mut small,
+        core::Size::new(ANALYSIS_WIDTH, height),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+    let mut hsv = core::Mat::default();
+    imgproc::cvt_color(&small, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+    let mut histogram = core::Mat::default();
+    imgproc::calc_hist(
+        &core::Vector::<core::Mat>::from_iter([hsv]),
+        &core::Vector::<i32>::from_slice(&[0, 1]),
+        &core::no_array(),
+        &mut histogram,
+        &core::Vector::<i32>::from_slice(&[30, 32]),
+        &core::Vector::<f32>::from_slice(&[0.0, 180.0, 0.0, 256.0]),
+        false,
+    )?;
+    Ok(histogram)
+}
+
+/// `frame` moved to the nearest cut within [`SNAP_DISTANCE`] frames. Starts snap to the
+/// first frame of a shot, ends to the last frame before a cut.
+pub fn snap(cuts: &[u32], frame: u32, end: bool) -> u32 {
+    cuts.iter()
+        .map(|cut| if end { cut.saturating_sub(1) } else { *cut })
+        .filter(|candidate| candidate.abs_diff(frame) <= SNAP_DISTANCE)
+        .min_by_key(|candidate| candidate.abs_diff(frame))
+        .unwrap_or(frame)
+}
+
+/// Inclusive frame ranges of the shots between `cuts` in a video of `total_frames` frames.
+pub fn shots(cuts: &[u32], total_frames: u32) -> Vec<(u32, u32)> {
+    let mut shots = Vec::new();
+    let mut start = 1;
+    for cut in cuts.iter().copied().filter(|cut| *cut <= total_frames) {
+        if cut > start {
+            shots.push((start, cut - 1));
+        }
+        start = cut;
+    }
+    if start <= total_frames {
+        shots.push((start, total_frames));
+    }
+    shots
+}
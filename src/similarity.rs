@@ -0,0 +1,440 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use opencv::{
+    core::{self, MatTraitConst, MatTraitConstManual},
+    imgproc,
+    videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
+};
+
+use crate::{library, project::Video, utils::load_video};
+
+/// One frame out of this many is indexed.
+pub const INDEX_STEP: u32 = 10;
+
+/// Number of frames a search returns.
+pub const SEARCH_RESULTS: usize = 24;
+
+/// Matches this close to a better match in the same video, or to the searched frame, are
+/// dropped since they mostly show the same moment.
+const MIN_MATCH_DISTANCE: u32 = 50;
+
+/// Width frames are scaled down to before computing their histograms.
+const ANALYSIS_WIDTH: i32 = 160;
+
+/// Hue, saturation and value bins of the color histogram.
+const HISTOGRAM_BINS: [i32; 3] = [8, 4, 4];
+
+/// Features of the indexed frames of a video.
+#[derive(Debug, Clone, Default)]
+pub struct VideoFeatures {
+    pub frames: Vec<u32>,
+    pub features: Vec<Vec<f32>>,
+}
+
+impl VideoFeatures {
+    /// Reads the features written by [`VideoFeatures::write`], `None` if there are none or
+    /// they were computed with other settings.
+    pub fn read(path: &Path) -> io::Result<Option<VideoFeatures>> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let words = bytes
+            .chunks_exact(4)
+            .map(|word| [word[0], word[1], word[2], word[3]])
+            .collect::<Vec<_>>();
+        let dimensions = feature_length();
+        let Some((header, rows)) = words.split_first() else {
+            return Ok(None);
+        };
+        if u32::from_le_bytes(*header) as usize != dimensions
+            || bytes.len() % 4 != 0
+            || rows.len() % (dimensions + 1) != 0
+        {
+            return Ok(None);
+        }
+        let mut features = VideoFeatures::default();
+        for row in rows.chunks_exact(dimensions + 1) {
+            features.frames.push(u32::from_le_bytes(row[0]));
+            features.features.push(
+                row[1..]
+                    .iter()
+                    .map(|word| f32::from_le_bytes(*word))
+                    .collect(),
+            );
+        }
+        Ok(Some(features))
+    }
+
+    /// Writes the number of dimensions followed by the frame and the features of each row,
+    /// all little endian.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = (feature_length() as u32).to_le_bytes().to_vec();
+        for (frame, features) in self.frames.iter().zip(&self.features) {
+            bytes.extend(frame.to_le_bytes());
+            for value in features {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, bytes)
+    }
+}
+
+/// An indexed frame similar to the searched one, from 0 (nothing in common) to 1.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub video: Video,
+    pub frame: u32,
+    pub similarity: f32,
+}
+
+/// Frame features of the project videos, computed in the background one video after the
+/// other and cached in the project folder.
+#[derive(Default)]
+pub struct FrameIndex {
+    shared: Arc<Mutex<Indexing>>,
+}
+
+#[derive(Default)]
+struct Indexing {
+    /// Videos with the paths of the video and of its feature cache.
+    queue: VecDeque<(Video, PathBuf, PathBuf)>,
+    /// Video being indexed and the share of its frames done.
+    current: Option<(Video, f32)>,
+    indexed: HashMap<Video, Arc<VideoFeatures>>,
+    running: bool,
+}
+
+impl FrameIndex {
+    /// Queues the videos that aren't queued or indexed yet and starts the worker.
+    pub fn request(&mut self, videos: &[(Video, PathBuf, PathBuf)], ctx: &egui::Context) {
+        let mut indexing = self.shared.lock().unwrap();
+        for (video, path, features_path) in videos {
+            let known = indexing.indexed.contains_key(video)
+                || indexing.queue.iter().any(|(queued, _, _)| queued == video)
+                || indexing
+                    .current
+                    .as_ref()
+                    .map_or(false, |(current, _)| current == video);
+            if !known {
+                indexing
+                    .queue
+                    .push_back((video.clone(), path.clone(), features_path.clone()));
+            }
+        }
+        if indexing.running || indexing.queue.is_empty() {
+            return;
+        }
+        indexing.running = true;
+        let shared = self.shared.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || loop {
+            let (video, path, features_path) = {
+                let mut indexing = shared.lock().unwrap();
+                let Some(next) = indexing.queue.pop_front() else {
+                    indexing.running = false;
+                    break;
+                };
+                indexing.current = Some((next.0.clone(), 0.0));
+                next
+            };
+            let cached = if is_current(&path, &features_path) {
+                VideoFeatures::read(&features_path)
+            } else {
+                Ok(None)
+            };
+            let features = match cached {
+                Ok(Some(features)) => features,
+                cached => {
+                    if let Err(e) = cached {
+                        eprintln!("Failed to read frame features of {}: {}", video, e);
+                    }
+                    let features = index_video(&path, |progress| {
+                        shared.lock().unwrap().current = Some((video.clone(), progress));
+                        ctx.request_repaint();
+                    });
+                    if let Err(e) = features.write(&features_path) {
+                        eprintln!("Failed to write frame features of {}: {}", video, e);
+                    }
+                    features
+                }
+            };
+            let mut indexing = shared.lock().unwrap();
+            indexing.current = None;
+            indexing.indexed.insert(video, Arc::new(features));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Video being indexed and the share of its frames done.
+    pub fn progress(&self) -> Option<(Video, f32)> {
+        self.shared.lock().unwrap().current.clone()
+    }
+
+    /// Number of videos indexed and of videos queued or being indexed.
+    pub fn counts(&self) -> (usize, usize) {
+        let indexing = self.shared.lock().unwrap();
+        (
+            indexing.indexed.len(),
+            indexing.queue.len() + indexing.current.iter().count(),
+        )
+    }
+
+    /// Features of the videos indexed so far, shared so that searches don't hold the lock.
+    fn indexed(&self) -> Vec<(Video, Arc<VideoFeatures>)> {
+        let indexing = self.shared.lock().unwrap();
+        indexing
+            .indexed
+            .iter()
+            .map(|(video, features)| (video.clone(), features.clone()))
+            .collect()
+    }
+}
+
+/// Indexed frames most similar to `query`, leaving out the frames around `frame` of
+/// `video` where the query comes from.
+fn search(
+    indexed: &[(Video, Arc<VideoFeatures>)],
+    query: &[f32],
+    video: &str,
+    frame: u32,
+) -> Vec<Match> {
+    let mut candidates = Vec::new();
+    for (indexed, features) in indexed {
+        for (candidate, values) in features.frames.iter().zip(&features.features) {
+            if indexed == video && candidate.abs_diff(frame) < MIN_MATCH_DISTANCE {
+                continue;
+            }
+            candidates.push(Match {
+                video: indexed.clone(),
+                frame: *candidate,
+                similarity: similarity(query, values),
+            });
+        }
+    }
+    candidates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    let mut matches: Vec<Match> = Vec::new();
+    for candidate in candidates {
+        if matches.len() == SEARCH_RESULTS {
+            break;
+        }
+        let near_better = matches.iter().any(|better| {
+            better.video == candidate.video
+                && better.frame.abs_diff(candidate.frame) < MIN_MATCH_DISTANCE
+        });
+        if !near_better {
+            matches.push(candidate);
+        }
+    }
+    matches
+}
+
+/// A similar-frame search running in the background, followed by reading the thumbnails
+/// of its matches. The search runs again whenever more videos have been indexed.
+pub struct SimilarFrames {
+    pub video: Video,
+    pub frame: u32,
+    query: Vec<f32>,
+    /// Paths of the project videos, to read the thumbnails from.
+    paths: HashMap<Video, PathBuf>,
+    /// Number of indexed videos the last search went through.
+    searched_videos: usize,
+    shared: Arc<Mutex<Search>>,
+}
+
+#[derive(Default)]
+struct Search {
+    /// Counts the searches, a search whose number is behind stops.
+    generation: usize,
+    /// Matches of the last finished search.
+    matches: Option<Vec<Match>>,
+    thumbnails: HashMap<(Video, u32), egui::TextureHandle>,
+}
+
+impl SimilarFrames {
+    /// Starts searching the indexed frames for ones like `query`, taken from `frame` of
+    /// `video`.
+    pub fn new(
+        video: Video,
+        frame: u32,
+        query: Vec<f32>,
+        paths: HashMap<Video, PathBuf>,
+        index: &FrameIndex,
+        ctx: &egui::Context,
+    ) -> Self {
+        let mut similar = SimilarFrames {
+            video,
+            frame,
+            query,
+            paths,
+            searched_videos: 0,
+            shared: Arc::default(),
+        };
+        similar.start_search(index, ctx);
+        similar
+    }
+
+    /// Searches again if videos were indexed since the last search.
+    pub fn update(&mut self, index: &FrameIndex, ctx: &egui::Context) {
+        if index.counts().0 != self.searched_videos {
+            self.start_search(index, ctx);
+        }
+    }
+
+    fn start_search(&mut self, index: &FrameIndex, ctx: &egui::Context) {
+        let indexed = index.indexed();
+        self.searched_videos = indexed.len();
+        let generation = {
+            let mut search = self.shared.lock().unwrap();
+            search.generation += 1;
+            search.generation
+        };
+        let (video, frame, query) = (self.video.clone(), self.frame, self.query.clone());
+        let paths = self.paths.clone();
+        let shared = self.shared.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let matches = search(&indexed, &query, &video, frame);
+            {
+                let mut search = shared.lock().unwrap();
+                if search.generation != generation {
+                    return;
+                }
+                search.matches = Some(matches.clone());
+            }
+            ctx.request_repaint();
+
+            let mut image = core::Mat::default();
+            for found in matches {
+                let key = (found.video, found.frame);
+                {
+                    let search = shared.lock().unwrap();
+                    if search.generation != generation {
+                        return;
+                    }
+                    if search.thumbnails.contains_key(&key) {
+                        continue;
+                    }
+                }
+                let Some(path) = paths.get(&key.0) else {
+                    continue;
+                };
+                let mut capture = load_video(path);
+                let _ = capture.set(videoio::CAP_PROP_POS_FRAMES, key.1.saturating_sub(1) as f64);
+                if !capture.read(&mut image).unwrap_or(false) || image.empty() {
+                    continue;
+                }
+                let name = format!("similar_{}_{}", path.display(), key.1);
+                if let Some(thumbnail) = library::thumbnail(name, &image, &ctx) {
+                    shared.lock().unwrap().thumbnails.insert(key, thumbnail);
+                    ctx.request_repaint();
+                }
+            }
+        });
+    }
+
+    /// Matches of the last search, `None` until the first search is done.
+    pub fn matches(&self) -> Option<Vec<Match>> {
+        self.shared.lock().unwrap().matches.clone()
+    }
+
+    /// Thumbnail of `found` once it has been read.
+    pub fn thumbnail(&self, found: &Match) -> Option<egui::TextureHandle> {
+        let search = self.shared.lock().unwrap();
+        search
+            .thumbnails
+            .get(&(found.video.clone(), found.frame))
+            .cloned()
+    }
+}
+
+/// Whether the features at `features_path` were written after the video at `path` last
+/// changed, otherwise the video has to be indexed again.
+fn is_current(path: &Path, features_path: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(path), modified(features_path)) {
+        (Ok(video), Ok(features)) => features >= video,
+        _ => false,
+    }
+}
+
+/// Features of every [`INDEX_STEP`]-th frame of `path`.
+pub fn index_video(path: &PathBuf, mut progress: impl FnMut(f32)) -> VideoFeatures {
+    let mut capture = load_video(path);
+    let total_frames = capture
+        .get(videoio::CAP_PROP_FRAME_COUNT)
+        .unwrap_or(0.0)
+        .max(1.0) as f32;
+    let mut features = VideoFeatures::default();
+    let mut frame = core::Mat::default();
+    let mut frame_number = 0;
+    loop {
+        frame_number += 1;
+        if (frame_number - 1) % INDEX_STEP != 0 {
+            if !capture.grab().unwrap_or(false) {
+                break;
+            }
+            continue;
+        }
+        if !capture.read(&mut frame).unwrap_or(false) || frame.empty() {
+            break;
+        }
+        if let Ok(values) = frame_features(&frame) {
+            features.frames.push(frame_number);
+            features.features.push(values);
+        }
+        if features.frames.len() % 20 == 0 {
+            progress(frame_number as f32 / total_frames);
+        }
+    }
+    features
+}
+
+/// Square roots of the normalized hue/saturation/value histogram of `frame`, so that the
+/// dot product of two frames' features is their Bhattacharyya coefficient.
+pub fn frame_features(frame: &core::Mat) -> opencv::Result<Vec<f32>> {
+    let size = frame.size()?;
+    let height = (size.height * ANALYSIS_WIDTH / size.width.max(1)).max(1);
+    let mut small = core::Mat::default();
+    imgproc::resize(
+        frame,
+        &mut small,
+        core::Size::new(ANALYSIS_WIDTH, height),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+    let mut hsv = core::Mat::default();
+    imgproc::cvt_color(&small, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+    let mut histogram = core::Mat::default();
+    imgproc::calc_hist(
+        &core::Vector::<core::Mat>::from_iter([hsv]),
+        &core::Vector::<i32>::from_slice(&[0, 1, 2]),
+        &core::no_array(),
+        &mut histogram,
+        &core::Vector::<i32>::from_slice(&HISTOGRAM_BINS),
+        &core::Vector::<f32>::from_slice(&[0.0, 180.0, 0.0, 256.0, 0.0, 256.0]),
+        false,
+    )?;
+    let counts = histogram.data_typed::<f32>()?;
+    let total = counts.iter().sum::<f32>().max(1.0);
+    Ok(counts.iter().map(|count| (count / total).sqrt()).collect())
+}
+
+fn feature_length() -> usize {
+    HISTOGRAM_BINS.iter().product::<i32>() as usize
+}
+
+fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
@@ -0,0 +1,343 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{params, Connection};
+
+use crate::{
+    app::FrameAnnotation,
+    project::{StorageLayout, Video},
+    utils::{storage_name, video_from_storage_name},
+};
+
+/// Annotation storage for projects that don't keep their annotations in `project.json`.
+pub trait AnnotationStore {
+    /// Videos that have annotations in the store.
+    fn videos(&self) -> io::Result<Vec<Video>>;
+
+    fn load(&self, video: &str) -> io::Result<Vec<FrameAnnotation>>;
+
+    /// Replaces all annotations of `video`, only writing the ones that changed.
+    fn replace(&mut self, video: &str, annotations: &[FrameAnnotation]) -> io::Result<()>;
+
+    /// Inserts `annotation` or updates the stored annotation with the same id.
+    fn put(&mut self, video: &str, annotation: &FrameAnnotation) -> io::Result<()> {
+        let mut annotations = self.load(video)?;
+        match annotations.iter_mut().find(|a| a.id == annotation.id) {
+            Some(stored) => *stored = annotation.clone(),
+            None => annotations.push(annotation.clone()),
+        }
+        self.replace(video, &annotations)
+    }
+
+    fn remove(&mut self, video: &str, id: &str) -> io::Result<()> {
+        let mut annotations = self.load(video)?;
+        annotations.retain(|annotation| annotation.id != id);
+        self.replace(video, &annotations)
+    }
+
+    /// Annotations with `label` that overlap the inclusive frame range `frames`.
+    fn query(
+        &self,
+        label: Option<&str>,
+        frames: Option<(u32, u32)>,
+    ) -> io::Result<Vec<(Video, FrameAnnotation)>> {
+        let mut matches = Vec::new();
+        for video in self.videos()? {
+            for annotation in self.load(&video)? {
+                if matches_query(&annotation, label, frames) {
+                    matches.push((video.clone(), annotation));
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Deletes everything the store wrote to disk, used after converting to another layout.
+    fn delete(self: Box<Self>) -> io::Result<()>;
+}
+
+pub fn matches_query(
+    annotation: &FrameAnnotation,
+    label: Option<&str>,
+    frames: Option<(u32, u32)>,
+) -> bool {
+    label.map_or(true, |label| annotation.label == label)
+        && frames.map_or(true, |(start, end)| {
+            annotation.start_frame <= end && annotation.end_frame >= start
+        })
+}
+
+/// Database file of [`StorageLayout::Sqlite`] in the labels folder.
+pub const SQLITE_FILE: &str = "annotations.sqlite";
+
+/// Opens the store of `layout` below `labels_dir`, `None` for [`StorageLayout::SingleFile`].
+pub fn open_store(
+    layout: StorageLayout,
+    labels_dir: &Path,
+) -> io::Result<Option<Box<dyn AnnotationStore>>> {
+    Ok(match layout {
+        StorageLayout::SingleFile => None,
+        StorageLayout::PerVideo => Some(Box::new(SidecarStore {
+            dir: labels_dir.to_path_buf(),
+        })),
+        StorageLayout::Sqlite => Some(Box::new(SqliteStore::open(&labels_dir.join(SQLITE_FILE))?)),
+    })
+}
+
+/// One `<video>.annotations.json` file per video.
+pub struct SidecarStore {
+    dir: PathBuf,
+}
+
+impl SidecarStore {
+    fn path(&self, video: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.annotations.json", storage_name(video)))
+    }
+}
+
+impl AnnotationStore for SidecarStore {
+    fn videos(&self) -> io::Result<Vec<Video>> {
+        let mut videos = Vec::new();
+        let mut dirs = vec![self.dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if let Some(name) = path
+                    .strip_prefix(&self.dir)
+                    .ok()
+                    .and_then(|relative| relative.to_str())
+                    .and_then(|relative| relative.strip_suffix(".annotations.json"))
+                {
+                    videos.push(video_from_storage_name(&name.replace('\\', "/")));
+                }
+            }
+        }
+        Ok(videos)
+    }
+
+    fn load(&self, video: &str) -> io::Result<Vec<FrameAnnotation>> {
+        match std::fs::read_to_string(self.path(video)) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn replace(&mut self, video: &str, annotations: &[FrameAnnotation]) -> io::Result<()> {
+        let path = self.path(video);
+        if annotations.is_empty() {
+            return match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        let content = serde_json::to_string_pretty(annotations)?;
+        let unchanged =
+            std::fs::read_to_string(&path).map_or(false, |existing| existing.trim_end() == content);
+        if unchanged {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // write through a temporary file so that readers never see partial content
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.write_all(b"\n")?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&temp_path, path)
+    }
+
+    fn delete(self: Box<Self>) -> io::Result<()> {
+        for video in self.videos()? {
+            std::fs::remove_file(self.path(&video))?;
+        }
+        Ok(())
+    }
+}
+
+/// All annotations in one SQLite database, indexed by label and frame range.
+///
+/// Each annotation is a row whose `data` column holds its JSON, so the schema doesn't
+/// have to follow every new annotation field.
+#[derive(Debug)]
+pub struct SqliteStore {
+    path: PathBuf,
+    connection: Connection,
+}
+
+fn sql_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path).map_err(sql_error)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS annotations (
+                    video TEXT NOT NULL,
+                    id TEXT NOT NULL,
+                    label TEXT NOT NULL,
+                    start_frame INTEGER NOT NULL,
+                    end_frame INTEGER NOT NULL,
+                    data TEXT NOT NULL,
+                    PRIMARY KEY (video, id)
+                );
+                CREATE INDEX IF NOT EXISTS annotations_label
+                    ON annotations (label, start_frame);
+                CREATE INDEX IF NOT EXISTS annotations_frames
+                    ON annotations (start_frame, end_frame);",
+            )
+            .map_err(sql_error)?;
+        Ok(SqliteStore {
+            path: path.to_path_buf(),
+            connection,
+        })
+    }
+
+    fn write(
+        connection: &Connection,
+        video: &str,
+        annotation: &FrameAnnotation,
+        data: &str,
+    ) -> rusqlite::Result<usize> {
+        connection.execute(
+            "INSERT OR REPLACE INTO annotations (video, id, label, start_frame, end_frame, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                video,
+                annotation.id,
+                annotation.label,
+                annotation.start_frame,
+                annotation.end_frame,
+                data
+            ],
+        )
+    }
+}
+
+impl AnnotationStore for SqliteStore {
+    fn videos(&self) -> io::Result<Vec<Video>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT DISTINCT video FROM annotations")
+            .map_err(sql_error)?;
+        let videos = statement
+            .query_map([], |row| row.get(0))
+            .map_err(sql_error)?
+            .collect::<rusqlite::Result<_>>()
+            .map_err(sql_error)?;
+        Ok(videos)
+    }
+
+    fn load(&self, video: &str) -> io::Result<Vec<FrameAnnotation>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT data FROM annotations WHERE video = ?1")
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map([video], |row| row.get::<_, String>(0))
+            .map_err(sql_error)?;
+        let mut annotations = Vec::new();
+        for data in rows {
+            annotations.push(serde_json::from_str(&data.map_err(sql_error)?)?);
+        }
+        Ok(annotations)
+    }
+
+    fn replace(&mut self, video: &str, annotations: &[FrameAnnotation]) -> io::Result<()> {
+        let transaction = self.connection.transaction().map_err(sql_error)?;
+        let mut stored: HashMap<String, String> = {
+            let mut statement = transaction
+                .prepare("SELECT id, data FROM annotations WHERE video = ?1")
+                .map_err(sql_error)?;
+            let rows = statement
+                .query_map([video], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(sql_error)?;
+            rows.collect::<rusqlite::Result<_>>().map_err(sql_error)?
+        };
+        for annotation in annotations {
+            let data = serde_json::to_string(annotation)?;
+            if stored.remove(&annotation.id).as_ref() != Some(&data) {
+                Self::write(&transaction, video, annotation, &data).map_err(sql_error)?;
+            }
+        }
+        for id in stored.keys() {
+            transaction
+                .execute(
+                    "DELETE FROM annotations WHERE video = ?1 AND id = ?2",
+                    params![video, id],
+                )
+                .map_err(sql_error)?;
+        }
+        transaction.commit().map_err(sql_error)
+    }
+
+    fn put(&mut self, video: &str, annotation: &FrameAnnotation) -> io::Result<()> {
+        let data = serde_json::to_string(annotation)?;
+        Self::write(&self.connection, video, annotation, &data).map_err(sql_error)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, video: &str, id: &str) -> io::Result<()> {
+        self.connection
+            .execute(
+                "DELETE FROM annotations WHERE video = ?1 AND id = ?2",
+                params![video, id],
+            )
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    fn query(
+        &self,
+        label: Option<&str>,
+        frames: Option<(u32, u32)>,
+    ) -> io::Result<Vec<(Video, FrameAnnotation)>> {
+        let mut sql = "SELECT video, data FROM annotations WHERE 1 = 1".to_string();
+        let mut values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if let Some(label) = label {
+            sql.push_str(" AND label = ?");
+            values.push(Box::new(label.to_string()));
+        }
+        if let Some((start, end)) = frames {
+            sql.push_str(" AND start_frame <= ? AND end_frame >= ?");
+            values.push(Box::new(end));
+            values.push(Box::new(start));
+        }
+        sql.push_str(" ORDER BY video, start_frame");
+        let mut statement = self.connection.prepare(&sql).map_err(sql_error)?;
+        let rows = statement
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(sql_error)?;
+        let mut matches = Vec::new();
+        for row in rows {
+            let (video, data) = row.map_err(sql_error)?;
+            matches.push((video, serde_json::from_str(&data)?));
+        }
+        Ok(matches)
+    }
+
+    fn delete(self: Box<Self>) -> io::Result<()> {
+        let path = self.path.clone();
+        self.connection.close().map_err(|(_, e)| sql_error(e))?;
+        std::fs::remove_file(path)
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    app::FrameAnnotation,
+    project::{Project, Video},
+    scores::FrameScores,
+};
+
+/// How the scores of a frame are turned into the model's uncertainty, from 0 (sure) to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UncertaintyMeasure {
+    /// Entropy of the scores, normalized by the entropy of a uniform distribution.
+    Entropy,
+    /// One minus the difference between the two best scores.
+    Margin,
+}
+
+impl UncertaintyMeasure {
+    pub const ALL: [UncertaintyMeasure; 2] =
+        [UncertaintyMeasure::Entropy, UncertaintyMeasure::Margin];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UncertaintyMeasure::Entropy => "entropy",
+            UncertaintyMeasure::Margin => "margin",
+        }
+    }
+
+    pub fn of(&self, scores: &[f32]) -> f32 {
+        // scores don't have to be probabilities, e.g. with independent sigmoid outputs
+        let total = scores.iter().map(|score| score.max(0.0)).sum::<f32>();
+        if scores.len() < 2 || total <= 0.0 {
+            return 0.0;
+        }
+        let probabilities = scores
+            .iter()
+            .map(|score| score.max(0.0) / total)
+            .collect::<Vec<_>>();
+        match self {
+            UncertaintyMeasure::Entropy => {
+                let entropy = probabilities
+                    .iter()
+                    .filter(|p| **p > 0.0)
+                    .map(|p| -p * p.ln())
+                    .sum::<f32>();
+                entropy / (probabilities.len() as f32).ln()
+            }
+            UncertaintyMeasure::Margin => {
+                let mut sorted = probabilities;
+                sorted.sort_by(|a, b| b.total_cmp(a));
+                1.0 - (sorted[0] - sorted[1])
+            }
+        }
+    }
+}
+
+/// A stretch of unlabeled frames, scored by the mean uncertainty of its frames.
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub video: Video,
+    pub start_frame: u32,
+    pub end_frame: u32,
+    pub uncertainty: f32,
+    /// Class with the highest mean score in the region.
+    pub guess: String,
+}
+
+/// Splits the unlabeled parts of `video` into regions of at most `region_frames` frames.
+pub fn uncertain_regions(
+    video: &str,
+    scores: &FrameScores,
+    annotations: &[FrameAnnotation],
+    measure: UncertaintyMeasure,
+    region_frames: u32,
+) -> Vec<Region> {
+    let is_labeled = |frame: u32| {
+        annotations
+            .iter()
+            .any(|annotation| annotation.is_labeled() && annotation.contains(frame))
+    };
+    let mut regions = Vec::new();
+    // rows of the region being collected
+    let mut rows: Vec<usize> = Vec::new();
+    let mut close = |rows: &mut Vec<usize>, end_frame: u32| {
+        if rows.is_empty() {
+            return;
+        }
+        let uncertainty = rows
+            .iter()
+            .map(|row| measure.of(&scores.row(*row)))
+            .sum::<f32>()
+            / rows.len() as f32;
+        let guess = (0..scores.classes.len())
+            .max_by(|a, b| {
+                let total = |class: usize| {
+                    rows.iter()
+                        .map(|row| scores.values[class][*row])
+                        .sum::<f32>()
+                };
+                total(*a).total_cmp(&total(*b))
+            })
+            .map_or(String::new(), |class| scores.classes[class].clone());
+        regions.push(Region {
+            video: video.to_string(),
+            start_frame: scores.frames[rows[0]],
+            end_frame,
+            uncertainty,
+            guess,
+        });
+        rows.clear();
+    };
+    for (row, frame) in scores.frames.iter().copied().enumerate() {
+        if is_labeled(frame) {
+            let end_frame = frame.saturating_sub(1);
+            close(&mut rows, end_frame);
+            continue;
+        }
+        if let Some(first) = rows.first() {
+            if frame - scores.frames[*first] >= region_frames {
+                close(&mut rows, frame - 1);
+            }
+        }
+        rows.push(row);
+    }
+    if let Some(last) = rows.last() {
+        let end_frame = scores.frames[*last];
+        close(&mut rows, end_frame);
+    }
+    regions
+}
+
+/// Unlabeled regions of all videos with scores, most uncertain first, visited one after
+/// the other from the labeling view.
+#[derive(Debug, Clone)]
+pub struct ReviewQueue {
+    pub regions: Vec<Region>,
+    pub position: usize,
+    pub measure: UncertaintyMeasure,
+}
+
+impl ReviewQueue {
+    pub fn build(project: &mut Project, measure: UncertaintyMeasure, region_frames: u32) -> Self {
+        project.load_all_annotations();
+        let mut regions = Vec::new();
+        for (video, _) in project.videos() {
+            // the model's own scores are probabilities, imported ones are used without them
+            let mut scores = FrameScores::read(&project.model_scores_path(&video));
+            if matches!(scores, Ok(None)) {
+                scores = FrameScores::read(&project.scores_path(&video));
+            }
+            let scores = match scores {
+                Ok(Some(scores)) => scores,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("Failed to read scores of {}: {}", video, e);
+                    continue;
+                }
+            };
+            let annotations = project
+                .annotations
+                .get(&video)
+                .map_or(&[][..], Vec::as_slice);
+            regions.extend(uncertain_regions(
+                &video,
+                &scores,
+                annotations,
+                measure,
+                region_frames.max(1),
+            ));
+        }
+        regions.sort_by(|a, b| b.uncertainty.total_cmp(&a.uncertainty));
+        ReviewQueue {
+            regions,
+            position: 0,
+            measure,
+        }
+    }
+
+    pub fn current(&self) -> Option<&Region> {
+        self.regions.get(self.position)
+    }
+
+    /// Moves to the next region that hasn't been labeled in the meantime.
+    pub fn advance(&mut self, project: &Project) -> Option<&Region> {
+        self.position += 1;
+        while let Some(region) = self.regions.get(self.position) {
+            let labeled = project
+                .annotations
+                .get(&region.video)
+                .into_iter()
+                .flatten()
+                .any(|annotation| {
+                    annotation.is_labeled()
+                        && annotation.start_frame <= region.start_frame
+                        && annotation.end_frame >= region.end_frame
+                });
+            if !labeled {
+                break;
+            }
+            self.position += 1;
+        }
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Scores of every frame from 0 to 9, leaning to `walk` before frame 6 and to `run`
+    /// after.
+    fn scores() -> FrameScores {
+        let walk = (0..10)
+            .map(|frame| if frame < 6 { 0.8 } else { 0.2 })
+            .collect::<Vec<_>>();
+        FrameScores {
+            classes: vec!["walk".to_string(), "run".to_string()],
+            frames: (0..10).collect(),
+            values: vec![walk.clone(), walk.iter().map(|score| 1.0 - score).collect()],
+        }
+    }
+
+    fn ranges(regions: &[Region]) -> Vec<(u32, u32, &str)> {
+        regions
+            .iter()
+            .map(|region| (region.start_frame, region.end_frame, region.guess.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn measures() {
+        assert_eq!(UncertaintyMeasure::Entropy.of(&[1.0, 0.0]), 0.0);
+        assert!((UncertaintyMeasure::Entropy.of(&[0.5, 0.5]) - 1.0).abs() < 1e-6);
+        assert!((UncertaintyMeasure::Entropy.of(&[0.8, 0.2]) - 0.7219).abs() < 1e-4);
+        assert!((UncertaintyMeasure::Margin.of(&[0.8, 0.2]) - 0.4).abs() < 1e-6);
+        // scores are normalized before measuring
+        assert!((UncertaintyMeasure::Margin.of(&[4.0, 4.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(UncertaintyMeasure::Margin.of(&[0.7]), 0.0);
+        assert_eq!(UncertaintyMeasure::Entropy.of(&[0.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn splits_around_labeled_frames() {
+        let annotations = [FrameAnnotation::new(3, 4, "walk".to_string())];
+        let regions = uncertain_regions(
+            "a.mp4",
+            &scores(),
+            &annotations,
+            UncertaintyMeasure::Margin,
+            4,
+        );
+        assert_eq!(
+            ranges(&regions),
+            [(0, 2, "walk"), (5, 8, "run"), (9, 9, "run")]
+        );
+        assert!(regions.iter().all(|region| region.video == "a.mp4"));
+        assert!((regions[0].uncertainty - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ignores_segments_without_a_label() {
+        let annotations = [FrameAnnotation::new(0, 9, String::new())];
+        let regions = uncertain_regions(
+            "a.mp4",
+            &scores(),
+            &annotations,
+            UncertaintyMeasure::Entropy,
+            10,
+        );
+        assert_eq!(ranges(&regions), [(0, 9, "walk")]);
+        assert!((regions[0].uncertainty - 0.7219).abs() < 1e-4);
+    }
+
+    #[test]
+    fn no_regions_in_labeled_videos() {
+        let annotations = [FrameAnnotation::new(0, 9, "walk".to_string())];
+        let regions = uncertain_regions(
+            "a.mp4",
+            &scores(),
+            &annotations,
+            UncertaintyMeasure::Entropy,
+            4,
+        );
+        assert!(regions.is_empty());
+    }
+}
@@ -1,7 +1,443 @@
-use std::path::PathBuf;
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashMap},
+    hash::{BuildHasher, Hasher},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use opencv::videoio::{self, VideoCapture};
+use serde::{Serialize, Serializer};
 
 pub fn load_video(path: &PathBuf) -> VideoCapture {
+    if let Some(pattern) = image_sequence_pattern(path) {
+        return videoio::VideoCapture::from_file(&pattern, videoio::CAP_IMAGES).unwrap();
+    }
     videoio::VideoCapture::from_file(path.to_string_lossy().as_ref(), videoio::CAP_ANY).unwrap()
 }
+
+pub const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "bmp", "tif", "tiff"];
+
+/// Splits `frame_00012.png` into `("frame_", "00012", "png")`.
+fn split_numbered(file_name: &str) -> Option<(&str, &str, &str)> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    if !IMAGE_EXTENSIONS
+        .iter()
+        .any(|image| extension.eq_ignore_ascii_case(image))
+    {
+        return None;
+    }
+    let prefix = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let digits = &stem[prefix.len()..];
+    (!digits.is_empty()).then_some((prefix, digits, extension))
+}
+
+/// If `dir` is a folder of numbered images such as camera frame dumps, the printf style
+/// pattern OpenCV opens it with, e.g. `dir/frame_%05d.png`.
+pub fn image_sequence_pattern(dir: &Path) -> Option<String> {
+    if !dir.is_dir() {
+        return None;
+    }
+    let mut names = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| split_numbered(name).is_some())
+        .collect::<Vec<_>>();
+    if names.len() < 2 {
+        return None;
+    }
+    names.sort();
+    let (prefix, digits, extension) = split_numbered(&names[0])?;
+    // zero padded numbers all have the width of the first one
+    let padded = names.iter().all(|name| {
+        split_numbered(name).map_or(false, |(_, other, _)| other.len() == digits.len())
+    });
+    let number = if padded && digits.len() > 1 {
+        format!("%0{}d", digits.len())
+    } else {
+        "%d".to_string()
+    };
+    let pattern = dir.join(format!("{}{}.{}", prefix, number, extension));
+    Some(pattern.to_string_lossy().to_string())
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Records of a CSV file with the line each starts on, skipping blank lines. Fields in
+/// quotes may hold commas, line breaks and doubled quotes, the others are trimmed.
+pub fn parse_csv(content: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    // inside quotes, and whether the current field was quoted
+    let (mut in_quotes, mut quoted) = (false, false);
+    let (mut line, mut record_line) = (1, 1);
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' if !quoted && field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+                quoted = true;
+            }
+            ',' | '\n' => {
+                let value = std::mem::take(&mut field);
+                let blank = c == '\n' && record.is_empty() && !quoted && value.trim().is_empty();
+                if !blank {
+                    record.push(if quoted {
+                        value
+                    } else {
+                        value.trim().to_string()
+                    });
+                }
+                quoted = false;
+                if c == '\n' {
+                    if !record.is_empty() {
+                        records.push((record_line, std::mem::take(&mut record)));
+                    }
+                    line += 1;
+                    record_line = line;
+                }
+            }
+            // text after the closing quote is left out, apart from stray characters
+            _ if quoted && c.is_whitespace() => {}
+            _ => field.push(c),
+        }
+    }
+    if quoted || !field.trim().is_empty() || !record.is_empty() {
+        record.push(if quoted {
+            field
+        } else {
+            field.trim().to_string()
+        });
+        records.push((record_line, record));
+    }
+    records
+}
+
+/// Stable color per label so the same label looks the same across sessions.
+pub fn label_color(label: &str) -> egui::Color32 {
+    let hash = fnv1a(label.as_bytes());
+    let hue = (hash % 360) as f32 / 360.0;
+    egui::ecolor::Hsva::new(hue, 0.6, 0.85, 1.0).into()
+}
+
+/// Bytes read from the start and the end of a file for its fingerprint.
+const FINGERPRINT_CHUNK: u64 = 1 << 20;
+
+/// Identifies a file by its content rather than its name: the size and an FNV-1a hash
+/// of the first and last MiB, which is enough to tell videos apart without reading them
+/// completely.
+pub fn fingerprint(path: &Path) -> std::io::Result<String> {
+    if path.is_dir() {
+        return sequence_fingerprint(path);
+    }
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut content = Vec::new();
+    (&mut file)
+        .take(FINGERPRINT_CHUNK)
+        .read_to_end(&mut content)?;
+    if size > FINGERPRINT_CHUNK {
+        file.seek(SeekFrom::Start(
+            size.saturating_sub(FINGERPRINT_CHUNK)
+                .max(FINGERPRINT_CHUNK),
+        ))?;
+        file.read_to_end(&mut content)?;
+    }
+    Ok(format!("{:x}-{:016x}", size, fnv1a(&content)))
+}
+
+/// Image sequences are fingerprinted by the names and sizes of their frames.
+fn sequence_fingerprint(dir: &Path) -> std::io::Result<String> {
+    let mut frames = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        frames.push((entry.file_name(), entry.metadata()?.len()));
+    }
+    frames.sort();
+    let mut content = Vec::new();
+    for (name, size) in &frames {
+        content.extend(name.to_string_lossy().bytes().chain(size.to_le_bytes()));
+    }
+    Ok(format!("seq{:x}-{:016x}", frames.len(), fnv1a(&content)))
+}
+
+/// Replaces `$NAME` and `${NAME}` path components with the value of the variable in
+/// `variables`, falling back to the environment.
+pub fn expand_path(path: &str, variables: &HashMap<String, String>) -> PathBuf {
+    let mut expanded = PathBuf::new();
+    for (i, component) in path.split('/').enumerate() {
+        let name = component
+            .strip_prefix("${")
+            .and_then(|name| name.strip_suffix('}'))
+            .or_else(|| component.strip_prefix('$'));
+        let value = name.and_then(|name| {
+            variables
+                .get(name)
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+        });
+        match value {
+            Some(value) => expanded.push(value),
+            // keep the root of absolute paths, `/` or a drive like `C:`
+            None if i == 0 && (component.is_empty() || component.ends_with(':')) => {
+                expanded.push(format!("{}/", component))
+            }
+            None => expanded.push(component),
+        }
+    }
+    expanded
+}
+
+/// Writes `path` with `/` separators, starting with `$NAME` if it lies below the value of
+/// one of `variables`, so that it points to the same footage on other machines.
+pub fn contract_path(path: &Path, variables: &HashMap<String, String>) -> String {
+    let best = variables
+        .iter()
+        .filter_map(|(name, value)| Some((name, path.strip_prefix(value).ok()?, value.len())))
+        .max_by_key(|(_, _, length)| *length);
+    let path = match best {
+        Some((name, relative, _)) => Path::new(&format!("${}", name)).join(relative),
+        None => path.to_path_buf(),
+    };
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Maps a video name to a path relative to the labels folder. External videos are named
+/// by absolute paths, which must not escape the labels folder.
+pub fn storage_name(video: &str) -> String {
+    let escaped = video.replace('%', "%25").replace(':', "%3A");
+    match escaped.strip_prefix('/') {
+        Some(rest) => format!("%2F{}", rest),
+        None => escaped,
+    }
+}
+
+/// Inverse of [`storage_name`].
+pub fn video_from_storage_name(name: &str) -> String {
+    let name = match name.strip_prefix("%2F") {
+        Some(rest) => format!("/{}", rest),
+        None => name.to_string(),
+    };
+    name.replace("%3A", ":").replace("%25", "%")
+}
+
+/// Formats seconds as `HH:MM:SS.mmm`.
+pub fn format_timecode(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Seconds at which `frame` is shown, 0 if the frame rate is unknown.
+pub fn frame_seconds(frame: u32, fps: f64) -> f64 {
+    if fps > 0.0 {
+        frame as f64 / fps
+    } else {
+        0.0
+    }
+}
+
+/// A frame number followed by its timecode, e.g. `123 (00:00:04.100)`, or just the
+/// number if the frame rate is unknown.
+pub fn format_frame(frame: u32, fps: f64) -> String {
+    if fps > 0.0 {
+        format!("{} ({})", frame, format_timecode(frame_seconds(frame, fps)))
+    } else {
+        frame.to_string()
+    }
+}
+
+/// Parses a frame number like `1234` or a timecode like `1:02:03.5` or `02:03` into a
+/// frame number at `fps` frames per second.
+pub fn parse_position(input: &str, fps: f64) -> Option<u32> {
+    let input = input.trim();
+    if !input.contains(':') {
+        return input.parse().ok();
+    }
+    let mut seconds = 0.0;
+    for part in input.split(':') {
+        let value: f64 = part.parse().ok()?;
+        if value < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    (fps > 0.0).then(|| (seconds * fps).round() as u32)
+}
+
+/// Seconds since the unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Formats a unix timestamp as `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+
+    // civil date from days since epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+/// 64-bit FNV-1a hash, stable across runs and machines unlike the std hashers.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Id of an annotation saved before annotations had ids, derived from its video and
+/// content so that every reader of the same file comes up with the same id. `occurrence`
+/// tells apart identical annotations of a video.
+pub fn legacy_id(
+    video: &str,
+    label: &str,
+    start_frame: u32,
+    end_frame: u32,
+    occurrence: u32,
+) -> String {
+    let key = format!(
+        "{}\n{}\n{}\n{}\n{}",
+        video, label, start_frame, end_frame, occurrence
+    );
+    format!("legacy{:016x}", fnv1a(key.as_bytes()))
+}
+
+/// Unique identifier built from the current time, a process-wide counter and random state.
+pub fn new_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(nanos);
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}{:016x}", nanos, hasher.finish())
+}
+
+/// Serializes a map with sorted keys so that saved files are deterministic.
+pub fn serialize_sorted<S: Serializer, V: Serialize>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frame_numbers() {
+        assert_eq!(parse_position("1234", 25.0), Some(1234));
+        assert_eq!(parse_position(" 42 ", 0.0), Some(42));
+        assert_eq!(parse_position("-3", 25.0), None);
+        assert_eq!(parse_position("12.5", 25.0), None);
+        assert_eq!(parse_position("", 25.0), None);
+    }
+
+    #[test]
+    fn parses_timecodes() {
+        assert_eq!(parse_position("02:03", 25.0), Some(3075));
+        assert_eq!(parse_position("1:02:03.5", 30.0), Some(111705));
+        assert_eq!(parse_position("0:00.04", 25.0), Some(1));
+        assert_eq!(parse_position("0:01", 29.97), Some(30));
+    }
+
+    #[test]
+    fn parses_csv() {
+        let records =
+            parse_csv("frame, walk ,\"run, fast\"\r\n\n1,\"say \"\"hi\"\"\",\n\"two\nlines\" ,x\n");
+        assert_eq!(
+            records,
+            [
+                (1, vec!["frame".into(), "walk".into(), "run, fast".into()]),
+                (3, vec!["1".into(), "say \"hi\"".into(), "".into()]),
+                (4, vec!["two\nlines".into(), "x".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_csv_without_a_final_line_break() {
+        assert_eq!(
+            parse_csv("a,b\n1,2"),
+            [
+                (1, vec!["a".into(), "b".into()]),
+                (2, vec!["1".into(), "2".into()])
+            ]
+        );
+        assert_eq!(parse_csv("\"\""), [(1, vec![String::new()])]);
+        assert!(parse_csv(" \n\r\n").is_empty());
+    }
+
+    #[test]
+    fn reads_written_csv_fields() {
+        let values = ["plain", "a, b", "say \"hi\"", "two\nlines"];
+        let line = values
+            .iter()
+            .map(|value| csv_field(value))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(parse_csv(&line), [(1, values.map(String::from).to_vec())]);
+    }
+
+    #[test]
+    fn rejects_invalid_timecodes() {
+        assert_eq!(parse_position("1:-5", 25.0), None);
+        assert_eq!(parse_position("1::5", 25.0), None);
+        assert_eq!(parse_position("a:05", 25.0), None);
+        assert_eq!(parse_position("1:00", 0.0), None);
+    }
+}
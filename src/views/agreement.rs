@@ -0,0 +1,242 @@
+use std::path::PathBuf;
+
+use crate::{
+    agreement::{compare, AgreementReport},
+    app::{FrameAnnotation, GlobalState},
+    project::Project,
+    utils::{format_frame, load_video},
+};
+use opencv::videoio::{self, VideoCaptureTraitConst};
+
+use super::{home::HomeView, label::LabelView, View};
+
+pub struct AgreementView {
+    video_name: String,
+    video_path: PathBuf,
+    total_frames: u32,
+    fps: f64,
+    annotator_a: Option<String>,
+    annotator_b: Option<String>,
+    iou_threshold: f64,
+    report: Option<AgreementReport>,
+}
+
+impl View for AgreementView {
+    fn render(&mut self, ctx: &egui::Context, app: &mut GlobalState) -> Option<Box<dyn View>> {
+        let mut next_view = None;
+        if let Some(project) = app.project.as_mut() {
+            // an unreadable video is reported on the home view and simply has no annotations here
+            let _ = project.load_video_annotations(&self.video_name);
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Back").clicked() {
+                    next_view = Some(Box::new(HomeView::new()) as Box<dyn View>);
+                }
+                ui.heading(format!("Agreement: {}", self.video_name));
+            });
+            selection_ui(ui, app, self);
+            if self.report.is_some() {
+                let next = report_ui(ui, app, self);
+                if next.is_some() {
+                    next_view = next;
+                }
+            }
+        });
+        next_view
+    }
+}
+
+impl AgreementView {
+    pub fn new(project: &Project, video_name: String, video_path: PathBuf) -> Self {
+        let capture = load_video(&video_path);
+        let total_frames = capture.get(videoio::CAP_PROP_FRAME_COUNT).unwrap_or(0.0) as u32;
+        let fps = capture.get(videoio::CAP_PROP_FPS).unwrap_or(0.0);
+        let assignees = project.assignees(&video_name);
+        Self {
+            annotator_a: assignees.first().cloned(),
+            annotator_b: assignees.get(1).cloned(),
+            video_name,
+            video_path,
+            total_frames,
+            fps,
+            iou_threshold: 0.5,
+            report: None,
+        }
+    }
+}
+
+fn annotations_of(
+    app: &GlobalState,
+    video: &str,
+    annotator: &Option<String>,
+) -> Vec<FrameAnnotation> {
+    app.project
+        .as_ref()
+        .unwrap()
+        .annotations
+        .get(video)
+        .into_iter()
+        .flatten()
+        .filter(|annotation| &annotation.author == annotator)
+        .cloned()
+        .collect()
+}
+
+fn selection_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut AgreementView) {
+    let mut annotators = app
+        .project
+        .as_ref()
+        .unwrap()
+        .assignees(&state.video_name)
+        .to_vec();
+    annotators.extend(app.annotators());
+    annotators.sort();
+    annotators.dedup();
+
+    ui.horizontal(|ui| {
+        for (id, selected) in [
+            ("annotator_a", &mut state.annotator_a),
+            ("annotator_b", &mut state.annotator_b),
+        ] {
+            egui::ComboBox::from_id_salt(id)
+                .selected_text(selected.clone().unwrap_or("-".to_string()))
+                .show_ui(ui, |ui| {
+                    for annotator in &annotators {
+                        ui.selectable_value(selected, Some(annotator.clone()), annotator);
+                    }
+                });
+        }
+        ui.label("IoU threshold");
+        ui.add(egui::Slider::new(&mut state.iou_threshold, 0.05..=1.0));
+        if ui.button("Compute").clicked() {
+            let a = annotations_of(app, &state.video_name, &state.annotator_a);
+            let b = annotations_of(app, &state.video_name, &state.annotator_b);
+            let total_frames = a
+                .iter()
+                .chain(b.iter())
+                .map(|annotation| annotation.end_frame as u64 + 1)
+                .max()
+                .unwrap_or(0)
+                .max(state.total_frames as u64);
+            state.report = Some(compare(&a, &b, total_frames, state.iou_threshold));
+        }
+    });
+}
+
+fn report_ui(
+    ui: &mut egui::Ui,
+    app: &mut GlobalState,
+    state: &mut AgreementView,
+) -> Option<Box<dyn View>> {
+    let mut next_view = None;
+    let report = state.report.as_ref().unwrap();
+
+    ui.separator();
+    ui.heading("Frame-level agreement");
+    egui::Grid::new("label_agreement")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Label");
+            ui.label("Cohen's kappa");
+            ui.label("Frames A");
+            ui.label("Frames B");
+            ui.label("Frames both");
+            ui.end_row();
+            for label in &report.labels {
+                ui.label(&label.label);
+                ui.label(format!("{:.3}", label.kappa));
+                ui.label(label.frames_a.to_string());
+                ui.label(label.frames_b.to_string());
+                ui.label(label.frames_both.to_string());
+                ui.end_row();
+            }
+        });
+
+    ui.separator();
+    ui.heading("Disagreements");
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(ui.available_width(), 16.0), egui::Sense::click());
+    let rect = response.rect;
+    let total_frames = report.total_frames.max(1) as f32;
+    let x = |frame: u64| rect.left() + rect.width() * frame as f32 / total_frames;
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    for disagreement in &report.disagreements {
+        painter.rect_filled(
+            egui::Rect::from_x_y_ranges(
+                x(disagreement.start_frame as u64)
+                    ..=x(disagreement.end_frame as u64 + 1)
+                        .max(x(disagreement.start_frame as u64) + 1.0),
+                rect.y_range(),
+            ),
+            0.0,
+            egui::Color32::RED,
+        );
+    }
+    if response.clicked() {
+        if let Some(position) = response.interact_pointer_pos() {
+            let frame = ((position.x - rect.left()) / rect.width() * total_frames).max(0.0) as u32;
+            next_view = Some(open_at(state, frame));
+        }
+    }
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::CollapsingHeader::new(format!("{} disagreeing ranges", report.disagreements.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                for disagreement in &report.disagreements {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{} - {}: [{}] vs [{}]",
+                            format_frame(disagreement.start_frame, state.fps),
+                            format_frame(disagreement.end_frame, state.fps),
+                            disagreement.labels_a.join(", "),
+                            disagreement.labels_b.join(", ")
+                        ));
+                        if ui.button("Open").clicked() {
+                            next_view = Some(open_at(state, disagreement.start_frame));
+                        }
+                    });
+                }
+            });
+
+        egui::CollapsingHeader::new("Segment matches").show(ui, |ui| {
+            let format_segment = |segment: Option<(u32, u32)>| {
+                segment.map_or("-".to_string(), |(start, end)| {
+                    format!(
+                        "{} - {}",
+                        format_frame(start, state.fps),
+                        format_frame(end, state.fps)
+                    )
+                })
+            };
+            egui::Grid::new("segment_matches")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Label");
+                    ui.label("A");
+                    ui.label("B");
+                    ui.label("IoU");
+                    ui.end_row();
+                    for segment_match in &report.matches {
+                        ui.label(&segment_match.label);
+                        ui.label(format_segment(segment_match.a));
+                        ui.label(format_segment(segment_match.b));
+                        ui.label(format!("{:.2}", segment_match.iou));
+                        ui.end_row();
+                    }
+                });
+        });
+    });
+    if next_view.is_some() {
+        // show both annotation sets at the disagreeing frames
+        app.annotator_filter = None;
+    }
+    next_view
+}
+
+fn open_at(state: &AgreementView, frame: u32) -> Box<dyn View> {
+    let mut view = LabelView::from_video_path(state.video_path.clone(), state.video_name.clone());
+    view.seek(frame);
+    Box::new(view)
+}
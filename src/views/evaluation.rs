@@ -0,0 +1,296 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use rfd::FileDialog;
+
+use crate::{
+    app::GlobalState,
+    evaluation::{evaluate, parse_thresholds, read_predictions, EvaluationReport},
+    utils::format_frame,
+};
+
+use super::{annotator_filter_ui, home::HomeView, label::LabelView, View};
+
+/// Outcome of an evaluation running in the background, `None` until it's done.
+type PendingEvaluation = Arc<Mutex<Option<Result<EvaluationReport, String>>>>;
+
+pub struct EvaluationView {
+    prediction_files: Vec<PathBuf>,
+    thresholds_input: String,
+    report: Option<EvaluationReport>,
+    pending: Option<PendingEvaluation>,
+    error: Option<String>,
+}
+
+impl View for EvaluationView {
+    fn render(&mut self, ctx: &egui::Context, app: &mut GlobalState) -> Option<Box<dyn View>> {
+        let mut next_view = None;
+        if let Some(project) = app.project.as_mut() {
+            project.load_all_annotations();
+        }
+        let done = self
+            .pending
+            .as_ref()
+            .and_then(|pending| pending.lock().unwrap().take());
+        if let Some(done) = done {
+            self.pending = None;
+            match done {
+                Ok(report) => self.report = Some(report),
+                Err(e) => self.error = Some(e),
+            }
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Back").clicked() {
+                    next_view = Some(Box::new(HomeView::new()) as Box<dyn View>);
+                }
+                ui.heading("Evaluation");
+            });
+            sources_ui(ui, app, self);
+            if let Some(error) = &self.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            if self.report.is_some() {
+                let next = report_ui(ui, app, self);
+                if next.is_some() {
+                    next_view = next;
+                }
+            }
+        });
+        next_view
+    }
+}
+
+impl EvaluationView {
+    pub fn new() -> Self {
+        Self {
+            prediction_files: Vec::new(),
+            thresholds_input: "0.3, 0.5, 0.7".to_string(),
+            report: None,
+            pending: None,
+            error: None,
+        }
+    }
+}
+
+fn sources_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut EvaluationView) {
+    ui.label("Prediction files: CSVs with video, label and start_frame/end_frame or frame columns, optionally a score.");
+    let mut to_remove = None;
+    for (i, path) in state.prediction_files.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(path.display().to_string());
+            if ui.small_button("Remove").clicked() {
+                to_remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = to_remove {
+        state.prediction_files.remove(i);
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Add prediction files...").clicked() {
+            if let Some(files) = FileDialog::new().add_filter("CSV", &["csv"]).pick_files() {
+                state.prediction_files.extend(files);
+            }
+        }
+        ui.label("IoU thresholds");
+        ui.add(egui::TextEdit::singleline(&mut state.thresholds_input).desired_width(120.0));
+        ui.label("Ground truth");
+        annotator_filter_ui(ui, app);
+        if ui
+            .add_enabled(
+                !state.prediction_files.is_empty() && state.pending.is_none(),
+                egui::Button::new("Evaluate"),
+            )
+            .clicked()
+        {
+            state.error = None;
+            let Some(thresholds) = parse_thresholds(&state.thresholds_input) else {
+                state.error = Some("IoU thresholds must lie in (0, 1]".to_string());
+                return;
+            };
+            let paths = state.prediction_files.clone();
+            let truth = app.project.as_ref().unwrap().annotations.clone();
+            let annotator = app.annotator_filter.clone();
+            let pending = PendingEvaluation::default();
+            let shared = pending.clone();
+            let ctx = ui.ctx().clone();
+            thread::spawn(move || {
+                let mut predictions = Vec::new();
+                let mut result = Ok(());
+                for path in &paths {
+                    match read_predictions(path) {
+                        Ok(read) => predictions.extend(read),
+                        Err(e) => {
+                            result = Err(format!("Failed to read {}: {}", path.display(), e));
+                            break;
+                        }
+                    }
+                }
+                let result = result
+                    .map(|_| evaluate(&truth, annotator.as_deref(), &predictions, &thresholds));
+                *shared.lock().unwrap() = Some(result);
+                ctx.request_repaint();
+            });
+            state.pending = Some(pending);
+        }
+        if state.pending.is_some() {
+            ui.spinner();
+        }
+        if state.report.is_some() && ui.button("Save report").clicked() {
+            if let Some(path) = FileDialog::new()
+                .add_filter("JSON", &["json"])
+                .set_file_name("evaluation.json")
+                .save_file()
+            {
+                let written = serde_json::to_string_pretty(state.report.as_ref().unwrap())
+                    .map_err(std::io::Error::from)
+                    .and_then(|content| std::fs::write(&path, content));
+                if let Err(e) = written {
+                    state.error = Some(format!("Failed to write {}: {}", path.display(), e));
+                }
+            }
+        }
+    });
+}
+
+fn report_ui(
+    ui: &mut egui::Ui,
+    app: &mut GlobalState,
+    state: &mut EvaluationView,
+) -> Option<Box<dyn View>> {
+    let mut next_view = None;
+    let report = state.report.as_ref().unwrap();
+
+    ui.separator();
+    match &report.annotator {
+        Some(annotator) => ui.label(format!("Ground truth: annotations of {}", annotator)),
+        None if report.merged_annotators > 1 => ui.colored_label(
+            egui::Color32::YELLOW,
+            format!(
+                "Ground truth: annotations of {} annotators merged, the scores can't reach 1 where they disagree. Pick an annotator to score against one.",
+                report.merged_annotators
+            ),
+        ),
+        None => ui.label("Ground truth: all annotations"),
+    };
+    ui.label(format!("{} videos evaluated", report.videos.len()));
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        ui.heading("Frame-level scores");
+        egui::Grid::new("label_metrics")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Label");
+                ui.label("Precision");
+                ui.label("Recall");
+                ui.label("F1");
+                ui.label("TP");
+                ui.label("FP");
+                ui.label("FN");
+                ui.end_row();
+                for metrics in &report.labels {
+                    ui.label(&metrics.label);
+                    ui.label(format!("{:.3}", metrics.precision));
+                    ui.label(format!("{:.3}", metrics.recall));
+                    ui.label(format!("{:.3}", metrics.f1));
+                    ui.label(metrics.true_positives.to_string());
+                    ui.label(metrics.false_positives.to_string());
+                    ui.label(metrics.false_negatives.to_string());
+                    ui.end_row();
+                }
+            });
+
+        egui::CollapsingHeader::new("Confusion matrix (frames, ground truth by prediction)").show(
+            ui,
+            |ui| {
+                egui::Grid::new("confusion_matrix")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("");
+                        for label in &report.confusion_labels {
+                            ui.label(label);
+                        }
+                        ui.end_row();
+                        for (label, row) in report.confusion_labels.iter().zip(&report.confusion) {
+                            ui.label(label);
+                            for count in row {
+                                ui.label(count.to_string());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            },
+        );
+
+        ui.heading("Segment-level average precision");
+        egui::Grid::new("average_precision")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Label");
+                for threshold in &report.iou_thresholds {
+                    ui.label(format!("AP@{:.2}", threshold));
+                }
+                ui.end_row();
+                for (label, precisions) in &report.average_precision {
+                    ui.label(label);
+                    for precision in precisions {
+                        ui.label(format!("{:.3}", precision));
+                    }
+                    ui.end_row();
+                }
+                ui.strong("mean");
+                for precision in &report.mean_average_precision {
+                    ui.strong(format!("{:.3}", precision));
+                }
+                ui.end_row();
+            });
+
+        ui.heading("Worst errors");
+        // frame rates come from the video library, which reads them in the background
+        let project = app.project.as_ref().unwrap();
+        let videos = report
+            .worst_errors
+            .iter()
+            .map(|error| (error.video.clone(), project.video_path(&error.video)))
+            .collect::<Vec<_>>();
+        app.library.request(&videos, ui.ctx());
+        egui::Grid::new("worst_errors")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Video");
+                ui.label("Frames");
+                ui.label("Label");
+                ui.label("Error");
+                ui.label("IoU");
+                ui.label("");
+                ui.end_row();
+                for error in &report.worst_errors {
+                    ui.label(&error.video);
+                    let fps = app.library.info(&error.video).map_or(0.0, |info| info.fps);
+                    ui.label(format!(
+                        "{} - {}",
+                        format_frame(error.start_frame, fps),
+                        format_frame(error.end_frame, fps)
+                    ));
+                    ui.label(&error.label);
+                    ui.label(error.kind.name());
+                    ui.label(format!("{:.2}", error.iou));
+                    if ui.button("Open").clicked() {
+                        let project = app.project.as_ref().unwrap();
+                        let path = project.video_path(&error.video);
+                        let mut view = LabelView::open(project, error.video.clone(), path);
+                        view.seek(error.start_frame);
+                        next_view = Some(Box::new(view) as Box<dyn View>);
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+    if next_view.is_some() {
+        app.annotator_filter = None;
+    }
+    next_view
+}
@@ -1,18 +1,144 @@
-use std::{collections::HashSet, fs};
+use std::{collections::HashSet, path::PathBuf};
 
 use rfd::FileDialog;
 
 use crate::{
     app::GlobalState,
-    project::{load_project_from_path, Project},
+    library::VideoInfo,
+    lock::ProjectLock,
+    model::ModelConfig,
+    project::{
+        load_project_from_path, AttributeDefinition, AttributeKind, Project, SaveError,
+        StorageLayout, Video, VideoStatus,
+    },
+    settings::Settings,
+    utils::{contract_path, format_timestamp},
 };
 
-use super::{label::LabelView, list::ListView, View};
+use super::{
+    agreement::AgreementView, evaluation::EvaluationView, label::LabelView, list::ListView,
+    merge::MergeView, queue::QueueView, status_badge, View,
+};
 
 pub struct HomeView {
     show_project_name_dialog: bool,
     new_project_name: String,
     show_labels_popup: bool,
+    attributes_label: Option<String>,
+    new_attribute_name: String,
+    new_attribute_kind: AttributeKind,
+    new_attribute_options: String,
+    show_settings_popup: bool,
+    assign_video: Option<String>,
+    assignees_input: String,
+    status_filter: Option<&'static str>,
+    stale_lock: Option<ProjectLock>,
+    save_error: Option<String>,
+    show_changed_on_disk_popup: bool,
+    show_formats_popup: bool,
+    formats_input: String,
+    /// Model settings being edited, the model popup is open while set.
+    model_input: Option<ModelConfig>,
+    class_names_input: String,
+    path_variables_input: String,
+    /// Videos of the project, scanned once since external roots can be large network shares.
+    video_list: Option<Vec<(Video, PathBuf)>>,
+    name_filter: String,
+    sort_column: SortColumn,
+    sort_ascending: bool,
+}
+
+/// One video in the library table.
+struct LibraryRow {
+    name: Video,
+    path: PathBuf,
+    info: Option<VideoInfo>,
+    annotation_count: usize,
+    coverage: f32,
+    status: VideoStatus,
+    last_edit: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortColumn {
+    Name,
+    Duration,
+    Fps,
+    Resolution,
+    Codec,
+    Annotations,
+    Coverage,
+    Status,
+    LastEdit,
+}
+
+impl SortColumn {
+    const ALL: [SortColumn; 9] = [
+        SortColumn::Name,
+        SortColumn::Duration,
+        SortColumn::Fps,
+        SortColumn::Resolution,
+        SortColumn::Codec,
+        SortColumn::Annotations,
+        SortColumn::Coverage,
+        SortColumn::Status,
+        SortColumn::LastEdit,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            SortColumn::Name => "Video",
+            SortColumn::Duration => "Duration",
+            SortColumn::Fps => "FPS",
+            SortColumn::Resolution => "Resolution",
+            SortColumn::Codec => "Codec",
+            SortColumn::Annotations => "Annotations",
+            SortColumn::Coverage => "Labeled",
+            SortColumn::Status => "Status",
+            SortColumn::LastEdit => "Last edit",
+        }
+    }
+
+    fn compare(&self, a: &LibraryRow, b: &LibraryRow) -> std::cmp::Ordering {
+        let info = |row: &LibraryRow, key: fn(&VideoInfo) -> f64| row.info.as_ref().map(key);
+        let ordering = match self {
+            SortColumn::Name => a.name.cmp(&b.name),
+            SortColumn::Duration => info(a, VideoInfo::duration)
+                .partial_cmp(&info(b, VideoInfo::duration))
+                .unwrap_or(std::cmp::Ordering::Equal),
+            SortColumn::Fps => info(a, |info| info.fps)
+                .partial_cmp(&info(b, |info| info.fps))
+                .unwrap_or(std::cmp::Ordering::Equal),
+            SortColumn::Resolution => info(a, |info| (info.width * info.height) as f64)
+                .partial_cmp(&info(b, |info| (info.width * info.height) as f64))
+                .unwrap_or(std::cmp::Ordering::Equal),
+            SortColumn::Codec => a
+                .info
+                .as_ref()
+                .map(|info| &info.codec)
+                .cmp(&b.info.as_ref().map(|info| &info.codec)),
+            SortColumn::Annotations => a.annotation_count.cmp(&b.annotation_count),
+            SortColumn::Coverage => a.coverage.total_cmp(&b.coverage),
+            SortColumn::Status => a.status.name().cmp(b.status.name()),
+            SortColumn::LastEdit => a.last_edit.cmp(&b.last_edit),
+        };
+        ordering.then_with(|| a.name.cmp(&b.name))
+    }
+}
+
+/// Formats seconds as `h:mm:ss`, or `m:ss` for videos shorter than an hour.
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
 }
 
 impl View for HomeView {
@@ -23,65 +149,407 @@ impl View for HomeView {
 
             ui.horizontal(|ui| {
                 if ui.button("Load Project").clicked() {
-                    if let Some(path) = FileDialog::new().pick_folder() {
-                        app.project = Some(load_project_from_path(&path));
+                    let loaded = FileDialog::new()
+                        .pick_folder()
+                        .map(|path| load_project_from_path(&path));
+                    match loaded {
+                        Some(Ok(project)) => {
+                            self.save_error = None;
+                            self.stale_lock = app.open_project(project);
+                            app.rescan_videos(ui.ctx());
+                            self.video_list = None;
+                            // ids given to legacy annotations are written once so they stay put
+                            let project = app.project.as_ref().unwrap();
+                            if project.filled_ids && !project.is_read_only() {
+                                save_project(self, app, false);
+                            }
+                        }
+                        Some(Err(e)) => {
+                            self.save_error = Some(format!("Failed to load project: {}", e));
+                        }
+                        None => {}
                     }
                 }
 
                 if ui.button("New Project").clicked() {
                     self.show_project_name_dialog = true;
                 }
+
+                if ui.button("Settings").clicked() {
+                    let mut variables = app
+                        .settings
+                        .path_variables
+                        .iter()
+                        .map(|(name, value)| format!("{}={}", name, value))
+                        .collect::<Vec<_>>();
+                    variables.sort();
+                    self.path_variables_input = variables.join("\n");
+                    self.show_settings_popup = true;
+                }
             });
 
+            if let Some(project) = app.project.as_mut() {
+                // projects saved before summaries were kept read their annotations once
+                if project.storage != StorageLayout::SingleFile
+                    && project.annotation_summaries.is_empty()
+                {
+                    project.load_all_annotations();
+                }
+            }
+            let mut save_requested = false;
+            let mut convert_requested = None;
+            let mut external_requested = ctx.input(|i| {
+                i.raw
+                    .dropped_files
+                    .iter()
+                    .filter_map(|file| file.path.clone())
+                    .collect::<Vec<_>>()
+            });
+            let mut rescan_requested = false;
             if let Some(project) = app.project.as_ref() {
                 ui.horizontal(|ui| {
                     if ui.button("show labels").clicked() {
                         self.show_labels_popup = true;
                     }
-                    if ui.button("Save").clicked() {
-                        let _ = fs::write(
-                            project.path.join("project.json"),
-                            serde_json::to_string(&project.config()).unwrap(),
-                        );
+                    if !project.is_read_only() && ui.button("Save").clicked() {
+                        save_requested = true;
                     }
                     if ui.button("Export").clicked() {
                         next_view = Some(Box::new(ListView::new()) as Box<dyn View>);
                     }
+                    if ui.button("Merge").clicked() {
+                        next_view = Some(Box::new(MergeView::new()) as Box<dyn View>);
+                    }
+                    if ui.button("Evaluate").clicked() {
+                        next_view = Some(Box::new(EvaluationView::new()) as Box<dyn View>);
+                    }
+                    if ui.button("Review queue").clicked() {
+                        next_view = Some(Box::new(QueueView::new(app)) as Box<dyn View>);
+                    }
+                    if ui.button("Continue labeling").clicked() {
+                        let videos = self.video_list.get_or_insert_with(|| project.videos());
+                        if let Some((video, path)) = project.next_unfinished(videos, None) {
+                            let mut view = LabelView::open(project, video, path);
+                            view.video_list = Some(videos.clone());
+                            next_view = Some(Box::new(view) as Box<dyn View>);
+                        }
+                    }
                 });
 
-                ui.label(format!("{}", project.path.display()));
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}", project.path.display()));
+                    if !project.is_read_only() {
+                        let mut storage = project.storage;
+                        egui::ComboBox::from_id_salt("storage_layout")
+                            .selected_text(storage_layout_name(storage))
+                            .show_ui(ui, |ui| {
+                                for layout in [
+                                    StorageLayout::SingleFile,
+                                    StorageLayout::PerVideo,
+                                    StorageLayout::Sqlite,
+                                ] {
+                                    ui.selectable_value(
+                                        &mut storage,
+                                        layout,
+                                        storage_layout_name(layout),
+                                    );
+                                }
+                            });
+                        if storage != project.storage {
+                            convert_requested = Some(storage);
+                        }
+                        if ui.button("Formats").clicked() {
+                            self.formats_input = project.video_extensions.join(", ");
+                            self.show_formats_popup = true;
+                        }
+                        if ui.button("Model").clicked() {
+                            let model = project.model.clone().unwrap_or_default();
+                            self.class_names_input = model.class_names.join("\n");
+                            self.model_input = Some(model);
+                        }
+                        if ui.button("Add videos").clicked() {
+                            if let Some(files) = FileDialog::new().pick_files() {
+                                external_requested.extend(files);
+                            }
+                        }
+                        if ui.button("Add folder").clicked() {
+                            if let Some(folder) = FileDialog::new().pick_folder() {
+                                external_requested.push(folder);
+                            }
+                        }
+                    }
+                    if app.fingerprinter.is_running() {
+                        ui.spinner();
+                        ui.label("looking for renamed videos");
+                    } else if ui.button("Rescan").clicked() {
+                        rescan_requested = true;
+                    }
+                    let detecting = app.shots.remaining();
+                    if detecting > 0 {
+                        ui.spinner();
+                        ui.label(format!("detecting shot cuts, {} videos left", detecting));
+                    } else if ui
+                        .button("Detect shot cuts")
+                        .on_hover_text(
+                            "Analyzes the videos without detected cuts in the background",
+                        )
+                        .clicked()
+                    {
+                        let videos = project
+                            .videos()
+                            .into_iter()
+                            .filter(|(video, _)| !project.shot_cuts.contains_key(video))
+                            .collect::<Vec<_>>();
+                        app.shots.request(&videos, ctx);
+                    }
+                });
+                if let Some(lock) = &project.locked_by {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!(
+                            "Read-only: locked by {} on {} (last seen {})",
+                            lock.holder,
+                            lock.host,
+                            format_timestamp(lock.heartbeat)
+                        ),
+                    );
+                }
+                if let Some(e) = &project.lock_error {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!("Read-only: the lock file can't be written ({})", e),
+                    );
+                }
+                if let Some(error) = &self.save_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                for (old, new) in &project.relinked_videos {
+                    ui.label(format!(
+                        "{} was renamed to {}, its labels moved along",
+                        old, new
+                    ));
+                }
+                let mut unreadable = project.unreadable_videos.iter().collect::<Vec<_>>();
+                unreadable.sort();
+                for (video, error) in unreadable {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        format!(
+                            "The annotations of {} can't be read and won't be saved: {}",
+                            video, error
+                        ),
+                    );
+                }
+                for (video, count) in &project.missing_videos {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!("{} annotations belong to missing video {}", count, video),
+                    );
+                }
 
-                let video_files = std::fs::read_dir(&project.path.join(&project.video_folder))
-                    .unwrap()
-                    .filter_map(|entry| entry.ok())
-                    .filter(|entry| {
-                        entry.path().extension().map_or(false, |ext| {
-                            ext.eq_ignore_ascii_case("mp4")
-                                || ext.eq_ignore_ascii_case("avi")
-                                || ext.eq_ignore_ascii_case("mov")
-                        })
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.name_filter);
+                    ui.label("Status");
+                    egui::ComboBox::from_id_salt("status_filter")
+                        .selected_text(self.status_filter.unwrap_or("all"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.status_filter, None, "all");
+                            for name in VideoStatus::NAMES {
+                                ui.selectable_value(&mut self.status_filter, Some(name), name);
+                            }
+                        });
+                });
+
+                let status_filter = self.status_filter;
+                let video_files = self
+                    .video_list
+                    .get_or_insert_with(|| project.videos())
+                    .iter()
+                    .filter(|(video_name, _)| {
+                        let status = project.status(video_name);
+                        status_filter.map_or(true, |name| status.name() == name)
                     })
+                    .cloned()
                     .collect::<Vec<_>>();
 
-                for video in video_files {
-                    ui.horizontal(|ui| {
-                        ui.label(video.file_name().to_string_lossy().to_string());
-
-                        if ui.button("Label").clicked() {
-                            next_view =
-                                Some(Box::new(LabelView::from_video_path(video.path()))
-                                    as Box<dyn View>);
+                app.library.request(&video_files, ctx);
+                let mut rows = video_files
+                    .into_iter()
+                    .filter(|(video_name, _)| {
+                        video_name
+                            .to_lowercase()
+                            .contains(&self.name_filter.to_lowercase())
+                    })
+                    .map(|(name, path)| {
+                        let summary = project.annotation_summary(&name);
+                        let info = app.library.info(&name);
+                        LibraryRow {
+                            coverage: info
+                                .as_ref()
+                                .map_or(0.0, |info| summary.coverage(info.frame_count)),
+                            annotation_count: summary.count,
+                            last_edit: summary.last_edit,
+                            status: project.status(&name),
+                            info,
+                            name,
+                            path,
                         }
-                    });
+                    })
+                    .collect::<Vec<_>>();
+                rows.sort_by(|a, b| {
+                    let ordering = self.sort_column.compare(a, b);
+                    if self.sort_ascending {
+                        ordering
+                    } else {
+                        ordering.reverse()
+                    }
+                });
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("video_library")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("");
+                            for column in SortColumn::ALL {
+                                let selected = self.sort_column == column;
+                                let title = match (selected, self.sort_ascending) {
+                                    (true, true) => format!("{} ^", column.title()),
+                                    (true, false) => format!("{} v", column.title()),
+                                    (false, _) => column.title().to_string(),
+                                };
+                                if ui.selectable_label(selected, title).clicked() {
+                                    if selected {
+                                        self.sort_ascending = !self.sort_ascending;
+                                    } else {
+                                        self.sort_column = column;
+                                        self.sort_ascending = true;
+                                    }
+                                }
+                            }
+                            ui.end_row();
+
+                            for row in rows {
+                                match row.info.as_ref().and_then(|info| info.thumbnail.as_ref()) {
+                                    Some(thumbnail) => {
+                                        ui.image((thumbnail.id(), thumbnail.size_vec2()));
+                                    }
+                                    None => {
+                                        ui.label("");
+                                    }
+                                }
+                                ui.label(&row.name);
+                                match &row.info {
+                                    Some(info) => {
+                                        ui.label(format_duration(info.duration()));
+                                        ui.label(format!("{:.2}", info.fps));
+                                        ui.label(format!("{}x{}", info.width, info.height));
+                                        ui.label(&info.codec);
+                                    }
+                                    None => {
+                                        for _ in 0..4 {
+                                            ui.label("...");
+                                        }
+                                    }
+                                }
+                                ui.label(row.annotation_count.to_string());
+                                ui.add(
+                                    egui::ProgressBar::new(row.coverage)
+                                        .desired_width(80.0)
+                                        .text(format!("{:.0}%", row.coverage * 100.0)),
+                                );
+                                status_badge(ui, &row.status);
+                                ui.label(row.last_edit.map_or("-".to_string(), format_timestamp));
+
+                                let video_name = row.name;
+                                let video_path = row.path;
+                                ui.horizontal(|ui| {
+                                    if ui.button("Label").clicked() {
+                                        if project.assignees(&video_name).len() > 1 {
+                                            app.annotator_filter = app.settings.annotator();
+                                        }
+                                        next_view = Some(Box::new(LabelView::open(
+                                            project,
+                                            video_name.clone(),
+                                            video_path.clone(),
+                                        ))
+                                            as Box<dyn View>);
+                                    }
+                                    if ui.button("Assign").clicked() {
+                                        self.assignees_input =
+                                            project.assignees(&video_name).join(", ");
+                                        self.assign_video = Some(video_name.clone());
+                                    }
+                                    let assignees = project.assignees(&video_name);
+                                    if !assignees.is_empty() {
+                                        ui.label(format!("assigned to {}", assignees.join(", ")));
+                                    }
+                                    if assignees.len() > 1 && ui.button("Agreement").clicked() {
+                                        next_view = Some(Box::new(AgreementView::new(
+                                            project,
+                                            video_name.clone(),
+                                            video_path.clone(),
+                                        ))
+                                            as Box<dyn View>);
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                        });
+                });
+            }
+
+            if save_requested {
+                save_project(self, app, false);
+            }
+            if let Some(project) = app.project.as_mut().filter(|p| !p.is_read_only()) {
+                let mut added = false;
+                for path in external_requested {
+                    added |= project.add_external(&path);
                 }
+                if added {
+                    self.video_list = None;
+                }
+            }
+            if rescan_requested {
+                app.rescan_videos(ui.ctx());
+                self.video_list = None;
+            }
+            if let Some(storage) = convert_requested {
+                let project = app.project.as_mut().unwrap();
+                self.save_error = project
+                    .convert_storage(storage)
+                    .err()
+                    .map(|e| e.to_string());
             }
 
             if self.show_project_name_dialog {
                 new_project_popup(ui, self, ctx, app);
             }
+            if self.stale_lock.is_some() {
+                stale_lock_popup(ui, self, ctx, app);
+            }
+            if self.show_changed_on_disk_popup {
+                changed_on_disk_popup(ui, self, ctx, app);
+            }
             if self.show_labels_popup {
                 labels_popup(ui, self, ctx, app);
             }
+            if self.attributes_label.is_some() {
+                attributes_popup(ui, self, ctx, app);
+            }
+            if self.show_settings_popup {
+                settings_popup(ui, self, ctx, app);
+            }
+            if self.assign_video.is_some() {
+                assign_popup(ui, self, ctx, app);
+            }
+            if self.show_formats_popup {
+                formats_popup(ui, self, ctx, app);
+            }
+            if self.model_input.is_some() {
+                model_popup(ui, self, ctx, app);
+            }
         });
         next_view
     }
@@ -93,6 +561,26 @@ impl HomeView {
             show_project_name_dialog: false,
             new_project_name: "".to_string(),
             show_labels_popup: false,
+            attributes_label: None,
+            new_attribute_name: String::new(),
+            new_attribute_kind: AttributeKind::Bool,
+            new_attribute_options: String::new(),
+            show_settings_popup: false,
+            assign_video: None,
+            assignees_input: String::new(),
+            status_filter: None,
+            stale_lock: None,
+            save_error: None,
+            show_changed_on_disk_popup: false,
+            show_formats_popup: false,
+            formats_input: String::new(),
+            model_input: None,
+            class_names_input: String::new(),
+            path_variables_input: String::new(),
+            video_list: None,
+            name_filter: String::new(),
+            sort_column: SortColumn::Name,
+            sort_ascending: true,
         }
     }
 }
@@ -115,25 +603,320 @@ fn new_project_popup(
             if ui.button("Create").clicked() {
                 if let Some(path) = FileDialog::new().pick_folder() {
                     let project_path = path.join(app.new_project_name.clone());
-                    let project = Project::with_root(project_path.clone());
+                    let mut project = Project::with_root(project_path.clone());
 
                     std::fs::create_dir_all(&project.path).unwrap();
                     std::fs::create_dir_all(&project.path.join("video")).unwrap();
                     std::fs::create_dir_all(&project.path.join("labels")).unwrap();
 
-                    let config = project.config();
-                    let _ = fs::write(
-                        project_path.join("project.json"),
-                        serde_json::to_string(&config).unwrap(),
-                    );
+                    if let Err(e) = project.save() {
+                        app.save_error = Some(e.to_string());
+                    }
 
-                    app_state.project = Some(project);
+                    app.stale_lock = app_state.open_project(project);
+                    app.video_list = None;
                     app.show_project_name_dialog = false;
                 }
             }
         });
 }
 
+fn save_project(app: &mut HomeView, app_state: &mut GlobalState, overwrite: bool) {
+    let project = app_state.project.as_mut().unwrap();
+    let result = if overwrite {
+        project.overwrite()
+    } else {
+        project.save()
+    };
+    app.save_error = None;
+    match result {
+        Ok(()) => {}
+        Err(SaveError::ChangedOnDisk) => app.show_changed_on_disk_popup = true,
+        Err(e) => app.save_error = Some(e.to_string()),
+    }
+}
+
+fn storage_layout_name(layout: StorageLayout) -> &'static str {
+    match layout {
+        StorageLayout::SingleFile => "single file",
+        StorageLayout::PerVideo => "file per video",
+        StorageLayout::Sqlite => "SQLite database",
+    }
+}
+
+fn stale_lock_popup(
+    _ui: &mut egui::Ui,
+    app: &mut HomeView,
+    ctx: &egui::Context,
+    app_state: &mut GlobalState,
+) {
+    let lock = app.stale_lock.clone().unwrap();
+    egui::Window::new("Project locked")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} on {} held this project, but their lock was last refreshed {}.",
+                lock.holder,
+                lock.host,
+                format_timestamp(lock.heartbeat)
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Take over").clicked() {
+                    app_state.take_over_lock();
+                    app.stale_lock = None;
+                }
+                if ui.button("Open read-only").clicked() {
+                    app.stale_lock = None;
+                }
+            });
+        });
+}
+
+fn changed_on_disk_popup(
+    _ui: &mut egui::Ui,
+    app: &mut HomeView,
+    ctx: &egui::Context,
+    app_state: &mut GlobalState,
+) {
+    egui::Window::new("Project changed on disk")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("project.json was modified by someone else since it was loaded.");
+            ui.horizontal(|ui| {
+                if ui.button("Overwrite").clicked() {
+                    app.show_changed_on_disk_popup = false;
+                    save_project(app, app_state, true);
+                }
+                if ui.button("Reload (discard my changes)").clicked() {
+                    let project = app_state.project.as_mut().unwrap();
+                    match load_project_from_path(&project.path) {
+                        Ok(mut reloaded) => {
+                            reloaded.locked_by = project.locked_by.take();
+                            reloaded.lock_error = project.lock_error.take();
+                            *project = reloaded;
+                        }
+                        Err(e) => app.save_error = Some(format!("Failed to reload project: {}", e)),
+                    }
+                    app.show_changed_on_disk_popup = false;
+                }
+                if ui.button("Cancel").clicked() {
+                    app.show_changed_on_disk_popup = false;
+                }
+            });
+        });
+}
+
+fn settings_popup(
+    _ui: &mut egui::Ui,
+    app: &mut HomeView,
+    ctx: &egui::Context,
+    app_state: &mut GlobalState,
+) {
+    egui::Window::new("Settings")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Annotator");
+                ui.text_edit_singleline(&mut app_state.settings.annotator);
+            });
+            ui.label("Path variables (NAME=/path, one per line)");
+            ui.text_edit_multiline(&mut app.path_variables_input);
+            ui.horizontal(|ui| {
+                if ui.button("Close").clicked() {
+                    app_state.settings = Settings::load();
+                    app.show_settings_popup = false;
+                }
+                if ui.button("Save").clicked() {
+                    app_state.settings.path_variables = app
+                        .path_variables_input
+                        .lines()
+                        .filter_map(|line| line.split_once('='))
+                        .map(|(name, value)| {
+                            let name = name.trim().trim_start_matches('$');
+                            (name.to_string(), value.trim().to_string())
+                        })
+                        .filter(|(name, value)| !name.is_empty() && !value.is_empty())
+                        .collect();
+                    app_state.settings.save();
+                    if let Some(project) = app_state.project.as_mut() {
+                        project.path_variables = app_state.settings.path_variables.clone();
+                        app.video_list = None;
+                    }
+                    app.show_settings_popup = false;
+                }
+            });
+        });
+}
+
+fn assign_popup(
+    _ui: &mut egui::Ui,
+    app: &mut HomeView,
+    ctx: &egui::Context,
+    app_state: &mut GlobalState,
+) {
+    let video = app.assign_video.clone().unwrap();
+    egui::Window::new(format!("Assign {}", video))
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("Annotators (comma separated)");
+            ui.text_edit_singleline(&mut app.assignees_input);
+            ui.horizontal(|ui| {
+                if ui.button("Close").clicked() {
+                    app.assign_video = None;
+                }
+                if ui.button("Set").clicked() {
+                    let assignees = app
+                        .assignees_input
+                        .split(',')
+                        .map(|annotator| annotator.trim().to_string())
+                        .filter(|annotator| !annotator.is_empty())
+                        .collect::<Vec<_>>();
+                    let project = app_state.project.as_mut().unwrap();
+                    if assignees.is_empty() {
+                        project.assignments.remove(&video);
+                    } else {
+                        project.assignments.insert(video.clone(), assignees);
+                    }
+                    app.assign_video = None;
+                }
+            });
+        });
+}
+
+fn formats_popup(
+    _ui: &mut egui::Ui,
+    app: &mut HomeView,
+    ctx: &egui::Context,
+    app_state: &mut GlobalState,
+) {
+    egui::Window::new("Video formats")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("File extensions (comma separated)");
+            ui.text_edit_singleline(&mut app.formats_input);
+            ui.label("Folders of numbered images are always listed as videos.");
+            ui.horizontal(|ui| {
+                if ui.button("Close").clicked() {
+                    app.show_formats_popup = false;
+                }
+                if ui.button("Set").clicked() {
+                    let extensions = app
+                        .formats_input
+                        .split(',')
+                        .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+                        .filter(|extension| !extension.is_empty())
+                        .collect::<Vec<_>>();
+                    app_state.project.as_mut().unwrap().video_extensions = extensions;
+                    app.video_list = None;
+                    app.show_formats_popup = false;
+                }
+            });
+        });
+}
+
+fn model_popup(
+    _ui: &mut egui::Ui,
+    app: &mut HomeView,
+    ctx: &egui::Context,
+    app_state: &mut GlobalState,
+) {
+    let project = app_state.project.as_mut().unwrap();
+    let model = app.model_input.as_mut().unwrap();
+    let mut close = false;
+    egui::Window::new("Pre-labeling model")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("ONNX file");
+                ui.text_edit_singleline(&mut model.path);
+                if ui.button("Browse").clicked() {
+                    if let Some(file) = FileDialog::new().add_filter("ONNX", &["onnx"]).pick_file()
+                    {
+                        let file = file.strip_prefix(&project.path).unwrap_or(&file);
+                        model.path = contract_path(file, &project.path_variables);
+                    }
+                }
+            });
+            egui::Grid::new("model_settings").show(ui, |ui| {
+                ui.label("Input size");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut model.input_width).range(1..=4096));
+                    ui.label("x");
+                    ui.add(egui::DragValue::new(&mut model.input_height).range(1..=4096));
+                });
+                ui.end_row();
+                ui.label("Mean");
+                ui.horizontal(|ui| {
+                    for value in model.mean.iter_mut() {
+                        ui.add(egui::DragValue::new(value).speed(0.5));
+                    }
+                });
+                ui.end_row();
+                ui.label("Scale");
+                ui.add(egui::DragValue::new(&mut model.scale).speed(0.0001));
+                ui.end_row();
+                ui.label("Channels");
+                ui.checkbox(&mut model.swap_rb, "RGB instead of BGR");
+                ui.end_row();
+                ui.label("Outputs");
+                ui.checkbox(&mut model.softmax, "apply softmax");
+                ui.end_row();
+                ui.label("Classify every");
+                ui.add(
+                    egui::DragValue::new(&mut model.frame_step)
+                        .range(1..=100)
+                        .suffix(" frames"),
+                );
+                ui.end_row();
+                ui.label("Smoothing window");
+                ui.add(
+                    egui::DragValue::new(&mut model.smoothing)
+                        .range(1..=101)
+                        .suffix(" predictions"),
+                );
+                ui.end_row();
+                ui.label("Min confidence");
+                ui.add(egui::Slider::new(&mut model.min_confidence, 0.0..=1.0));
+                ui.end_row();
+                ui.label("Min segment length");
+                ui.add(egui::DragValue::new(&mut model.min_segment_length).suffix(" frames"));
+                ui.end_row();
+            });
+            ui.label("Class names, one per output (empty lines are never proposed)");
+            ui.text_edit_multiline(&mut app.class_names_input);
+            ui.horizontal(|ui| {
+                if ui.button("Close").clicked() {
+                    close = true;
+                }
+                if project.model.is_some() && ui.button("Remove").clicked() {
+                    project.model = None;
+                    close = true;
+                }
+                if ui
+                    .add_enabled(!model.path.is_empty(), egui::Button::new("Set"))
+                    .clicked()
+                {
+                    model.class_names = app
+                        .class_names_input
+                        .lines()
+                        .map(|name| name.trim().to_string())
+                        .collect();
+                    project.model = Some(model.clone());
+                    close = true;
+                }
+            });
+        });
+    if close {
+        app.model_input = None;
+    }
+}
+
 fn labels_popup(
     _ui: &mut egui::Ui,
     app: &mut HomeView,
@@ -145,13 +928,19 @@ fn labels_popup(
         .resizable(false)
         .show(ctx, |ui| {
             for label in app_state.project.as_ref().unwrap().used_labels.iter() {
-                ui.label(label);
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    if ui.button("Attributes").clicked() {
+                        app.attributes_label = Some(label.clone());
+                    }
+                });
             }
             ui.horizontal(|ui| {
                 if ui.button("Close").clicked() {
                     app.show_labels_popup = false;
                 }
                 if ui.button("Reload").clicked() {
+                    app_state.project.as_mut().unwrap().load_all_annotations();
                     let mut used_labels = HashSet::new();
                     for (_, annotations) in app_state.project.as_mut().unwrap().annotations.iter() {
                         for annotation in annotations.iter() {
@@ -163,3 +952,99 @@ fn labels_popup(
             });
         });
 }
+
+fn attributes_popup(
+    _ui: &mut egui::Ui,
+    app: &mut HomeView,
+    ctx: &egui::Context,
+    app_state: &mut GlobalState,
+) {
+    let label = app.attributes_label.clone().unwrap();
+    let definitions = app_state
+        .project
+        .as_mut()
+        .unwrap()
+        .label_attributes
+        .entry(label.clone())
+        .or_default();
+
+    egui::Window::new(format!("Attributes of {}", label))
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let mut to_delete = None;
+            for (i, definition) in definitions.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(&definition.name);
+                    ui.label(attribute_kind_name(&definition.kind));
+                    if let AttributeKind::Enum(options) = &definition.kind {
+                        ui.label(options.join(", "));
+                    }
+                    if ui.button("Delete").clicked() {
+                        to_delete = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = to_delete {
+                definitions.remove(i);
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut app.new_attribute_name);
+                egui::ComboBox::from_id_salt("new_attribute_kind")
+                    .selected_text(attribute_kind_name(&app.new_attribute_kind))
+                    .show_ui(ui, |ui| {
+                        for kind in [
+                            AttributeKind::Bool,
+                            AttributeKind::Enum(vec![]),
+                            AttributeKind::Number,
+                            AttributeKind::Text,
+                        ] {
+                            let name = attribute_kind_name(&kind);
+                            ui.selectable_value(&mut app.new_attribute_kind, kind, name);
+                        }
+                    });
+            });
+            if let AttributeKind::Enum(_) = app.new_attribute_kind {
+                ui.horizontal(|ui| {
+                    ui.label("Options (comma separated)");
+                    ui.text_edit_singleline(&mut app.new_attribute_options);
+                });
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Close").clicked() {
+                    app.attributes_label = None;
+                }
+                if ui.button("Add").clicked() && !app.new_attribute_name.is_empty() {
+                    let kind = match app.new_attribute_kind {
+                        AttributeKind::Enum(_) => AttributeKind::Enum(
+                            app.new_attribute_options
+                                .split(',')
+                                .map(|option| option.trim().to_string())
+                                .filter(|option| !option.is_empty())
+                                .collect(),
+                        ),
+                        ref kind => kind.clone(),
+                    };
+                    definitions.retain(|definition| definition.name != app.new_attribute_name);
+                    definitions.push(AttributeDefinition {
+                        name: app.new_attribute_name.clone(),
+                        kind,
+                    });
+                    app.new_attribute_name.clear();
+                    app.new_attribute_options.clear();
+                }
+            });
+        });
+}
+
+fn attribute_kind_name(kind: &AttributeKind) -> &'static str {
+    match kind {
+        AttributeKind::Bool => "bool",
+        AttributeKind::Enum(_) => "enum",
+        AttributeKind::Number => "number",
+        AttributeKind::Text => "text",
+    }
+}
@@ -1,8 +1,19 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::SystemTime,
+};
 
 use crate::{
-    app::{FrameAnnotation, GlobalState},
-    utils::load_video,
+    app::{AnnotationKind, AttributeValue, BoundingBox, FrameAnnotation, GlobalState},
+    library::THUMBNAIL_WIDTH,
+    project::{AttributeDefinition, AttributeKind, LabelSession, Project, Video, VideoStatus},
+    scores::FrameScores,
+    shots,
+    similarity::{frame_features, SimilarFrames},
+    utils::{
+        format_frame, format_timecode, format_timestamp, label_color, load_video, parse_position,
+    },
 };
 use egui_autocomplete::AutoCompleteTextEdit;
 use opencv::{
@@ -10,8 +21,11 @@ use opencv::{
     imgproc,
     videoio::{self, VideoCapture, VideoCaptureTrait, VideoCaptureTraitConst},
 };
+use rfd::FileDialog;
 
-use super::{home::HomeView, list::ListView, View};
+use super::{
+    annotator_filter_ui, home::HomeView, list::ListView, queue::open_current, status_badge, View,
+};
 
 pub struct LabelView {
     capture: VideoCapture,
@@ -19,58 +33,265 @@ pub struct LabelView {
     is_playing: bool,
     current_start_frame: Option<u32>,
     current_end_frame: Option<u32>,
+    current_event_frame: Option<u32>,
+    event_tolerance: u32,
+    selected_annotation: Option<String>,
+    box_drag_start: Option<egui::Pos2>,
+    show_reject_popup: bool,
+    reject_comment: String,
+    /// Asks before a new pre-labeling run replaces the pending proposals.
+    show_prelabel_popup: bool,
     show_label_popup: bool,
     label_input: String,
     video_name: String,
+    /// How many times the timeline is magnified around the current frame.
+    timeline_zoom: f32,
+    jump_label: String,
+    goto_input: String,
+    goto_error: bool,
+    fps: f64,
+    start_input: String,
+    end_input: String,
+    snap_to_cuts: bool,
+    /// Imported or computed prediction scores, read again whenever their file changes.
+    scores: Option<FrameScores>,
+    /// File and modification time the scores were read from.
+    scores_loaded: Option<(PathBuf, SystemTime)>,
+    /// Shows the scores computed by the project's model instead of imported ones.
+    model_scores: bool,
+    scores_error: Option<String>,
+    /// Draws scores as lines instead of a heatmap.
+    score_lines: bool,
+    score_class: usize,
+    score_threshold: f32,
+    score_min_length: u32,
+    /// Label typed for each unlabeled segment, by annotation id.
+    segment_label_inputs: HashMap<String, String>,
+    /// Region of the review queue being looked at, outlined on the timeline.
+    focus_region: Option<(u32, u32)>,
+    /// Project videos scanned for "Next unfinished", passed on to the next video's view.
+    pub video_list: Option<Vec<(Video, PathBuf)>>,
+}
+
+/// Jumps triggered from the keyboard or the navigation bar.
+#[derive(Clone, Copy)]
+enum Jump {
+    Boundary { forward: bool },
+    Label { forward: bool },
+    Gap,
 }
 
 impl View for LabelView {
     fn render(&mut self, ctx: &egui::Context, app: &mut GlobalState) -> Option<Box<dyn View>> {
         let mut next_view = None;
+        if let Some(project) = app.project.as_mut() {
+            if let Err(e) = project.load_video_annotations(&self.video_name) {
+                return unreadable_ui(ctx, project, &self.video_name, &e);
+            }
+        }
+        egui::SidePanel::right("annotation_panel").show(ctx, |ui| {
+            annotation_panel(ui, app, self);
+        });
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 if ui.button("Back").clicked() {
                     next_view = Some(Box::new(HomeView::new()) as Box<dyn View>);
                 }
                 ui.heading("Label");
+                ui.separator();
+                annotator_filter_ui(ui, app);
+                ui.separator();
+                review_ui(ui, app, self);
+                ui.separator();
+                if let Some(queue) = &app.review_queue {
+                    let text = format!(
+                        "Next in queue ({}/{})",
+                        queue.position + 1,
+                        queue.regions.len()
+                    );
+                    if ui.button(text).clicked() {
+                        let project = app.project.as_ref().unwrap();
+                        app.review_queue.as_mut().unwrap().advance(project);
+                        next_view = open_current(app);
+                    }
+                }
+                if ui.button("Next unfinished").clicked() {
+                    let project = app.project.as_ref().unwrap();
+                    let videos = self.video_list.get_or_insert_with(|| project.videos());
+                    if let Some((video, path)) =
+                        project.next_unfinished(videos, Some(&self.video_name))
+                    {
+                        let mut view = LabelView::open(project, video, path);
+                        view.video_list = self.video_list.take();
+                        next_view = Some(Box::new(view) as Box<dyn View>);
+                    }
+                }
             });
             let next = playback_ui(ui, self);
             if next.is_some() {
                 next_view = next;
             }
+            navigation_ui(ui, app, self);
+            shots_ui(ui, app, self);
+            proposals_ui(ui, app, self);
+            scores_ui(ui, app, self);
+            let next = similar_ui(ui, app, self);
+            if next.is_some() {
+                next_view = next;
+            }
+            timeline_ui(ui, app, self);
             video_ui(ui, app, self);
             label_ui(ui, app, self);
             label_popup(ui, ctx, self, app);
-            let next = controls(ctx, self);
+            reject_popup(ctx, self, app);
+            prelabel_popup(ctx, self, app);
+            let next = controls(ctx, app, self);
             if next.is_some() {
                 next_view = next;
             }
         });
+        let session = self.session();
+        if let Some(project) = app.project.as_mut() {
+            if project.sessions.get(&self.video_name) != Some(&session) {
+                project.sessions.insert(self.video_name.clone(), session);
+            }
+        }
         next_view
     }
 }
 
 impl LabelView {
-    pub fn from_video_path(path: PathBuf) -> Self {
+    pub fn from_video_path(path: PathBuf, video_name: String) -> Self {
         let capture = load_video(&path);
+        let fps = capture.get(videoio::CAP_PROP_FPS).unwrap_or(0.0);
         Self {
             capture,
             current_frame: None,
             is_playing: false,
             current_start_frame: None,
             current_end_frame: None,
+            current_event_frame: None,
+            event_tolerance: 0,
+            selected_annotation: None,
+            box_drag_start: None,
+            show_reject_popup: false,
+            show_prelabel_popup: false,
+            reject_comment: String::new(),
             show_label_popup: false,
             label_input: String::new(),
-            video_name: path.file_name().unwrap().to_string_lossy().to_string(),
+            video_name,
+            timeline_zoom: 1.0,
+            jump_label: String::new(),
+            goto_input: String::new(),
+            goto_error: false,
+            fps,
+            start_input: String::new(),
+            end_input: String::new(),
+            snap_to_cuts: true,
+            scores: None,
+            scores_loaded: None,
+            model_scores: false,
+            scores_error: None,
+            score_lines: false,
+            score_class: 0,
+            score_threshold: 0.5,
+            score_min_length: 5,
+            segment_label_inputs: HashMap::new(),
+            focus_region: None,
+            video_list: None,
         }
     }
+
+    /// Opens `video_name` where its last labeling session stopped.
+    pub fn open(project: &Project, video_name: String, path: PathBuf) -> Self {
+        let mut view = Self::from_video_path(path, video_name);
+        if let Some(session) = project.sessions.get(&view.video_name) {
+            view.current_start_frame = session.start_frame;
+            view.current_end_frame = session.end_frame;
+            view.timeline_zoom = session.timeline_zoom;
+            view.seek(session.frame);
+        }
+        view
+    }
+
+    /// Seeks to the start of the region `start..=end` and outlines it on the timeline.
+    pub fn focus(&mut self, start: u32, end: u32) {
+        self.focus_region = Some((start, end));
+        self.seek(start);
+    }
+
+    fn session(&self) -> LabelSession {
+        LabelSession {
+            frame: self
+                .capture
+                .get(videoio::CAP_PROP_POS_FRAMES)
+                .unwrap_or(0.0) as u32,
+            start_frame: self.current_start_frame,
+            end_frame: self.current_end_frame,
+            timeline_zoom: self.timeline_zoom,
+        }
+    }
+}
+
+/// Shown instead of the labeling UI when the annotations of the video can't be read, so
+/// that nothing is edited that would replace them.
+fn unreadable_ui(
+    ctx: &egui::Context,
+    project: &mut Project,
+    video: &str,
+    error: &str,
+) -> Option<Box<dyn View>> {
+    let mut next_view = None;
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("Back").clicked() {
+                next_view = Some(Box::new(HomeView::new()) as Box<dyn View>);
+            }
+            ui.heading(format!("Label: {}", video));
+        });
+        ui.colored_label(
+            egui::Color32::RED,
+            format!("Failed to read the annotations of {}: {}", video, error),
+        );
+        ui.label("Nothing is saved for this video until its annotations can be read again.");
+        if ui.button("Retry").clicked() {
+            let _ = project.retry_video_annotations(video);
+        }
+    });
+    next_view
 }
 
-fn controls(ctx: &egui::Context, state: &mut LabelView) -> Option<Box<dyn View>> {
+fn controls(
+    ctx: &egui::Context,
+    app: &mut GlobalState,
+    state: &mut LabelView,
+) -> Option<Box<dyn View>> {
     let mut next_view = None;
-    if !state.show_label_popup {
+    let mut jump = None;
+    let cuts = if state.snap_to_cuts {
+        app.project
+            .as_ref()
+            .unwrap()
+            .shot_cuts
+            .get(&state.video_name)
+            .cloned()
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    if !state.show_label_popup && !ctx.wants_keyboard_input() {
         ctx.input(|i| {
-            if i.key_pressed(egui::Key::Space) {
+            if i.key_pressed(egui::Key::CloseBracket) {
+                jump = Some(Jump::Boundary { forward: true });
+            } else if i.key_pressed(egui::Key::OpenBracket) {
+                jump = Some(Jump::Boundary { forward: false });
+            } else if i.key_pressed(egui::Key::N) {
+                jump = Some(Jump::Label {
+                    forward: !i.modifiers.shift,
+                });
+            } else if i.key_pressed(egui::Key::G) {
+                jump = Some(Jump::Gap);
+            } else if i.key_pressed(egui::Key::Space) {
                 state.is_playing = !state.is_playing;
             } else if i.key_pressed(egui::Key::ArrowRight) {
                 let step = if i.modifiers.shift { 10 } else { 1 };
@@ -82,6 +303,7 @@ fn controls(ctx: &egui::Context, state: &mut LabelView) -> Option<Box<dyn View>>
                 next_view = Some(Box::new(HomeView::new()) as Box<dyn View>);
             } else if i.key_pressed(egui::Key::S) {
                 let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+                let current_frame = shots::snap(&cuts, current_frame, false);
                 if let Some(end_frame) = state.current_end_frame {
                     if current_frame <= end_frame {
                         state.current_start_frame = Some(current_frame);
@@ -91,6 +313,7 @@ fn controls(ctx: &egui::Context, state: &mut LabelView) -> Option<Box<dyn View>>
                 }
             } else if i.key_pressed(egui::Key::E) {
                 let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+                let current_frame = shots::snap(&cuts, current_frame, true);
                 if let Some(start_frame) = state.current_start_frame {
                     if current_frame >= start_frame {
                         state.current_end_frame = Some(current_frame);
@@ -99,15 +322,511 @@ fn controls(ctx: &egui::Context, state: &mut LabelView) -> Option<Box<dyn View>>
                     state.current_end_frame = Some(current_frame);
                 }
             } else if i.key_pressed(egui::Key::L) {
-                if state.current_start_frame.is_some() && state.current_end_frame.is_some() {
+                if let (Some(start), Some(end)) =
+                    (state.current_start_frame, state.current_end_frame)
+                {
+                    if start > end {
+                        return;
+                    }
+                    state.current_event_frame = None;
                     state.show_label_popup = true;
                 }
+            } else if i.key_pressed(egui::Key::P) {
+                let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+                state.current_event_frame = Some(current_frame);
+                state.show_label_popup = true;
+            }
+        });
+    }
+    if let Some(jump) = jump {
+        jump_to(app, state, jump);
+    }
+    next_view
+}
+
+fn navigation_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
+    ui.horizontal(|ui| {
+        let mut jump = None;
+        if ui.button("[ Boundary").clicked() {
+            jump = Some(Jump::Boundary { forward: false });
+        }
+        if ui.button("Boundary ]").clicked() {
+            jump = Some(Jump::Boundary { forward: true });
+        }
+        if ui.button("Next gap (G)").clicked() {
+            jump = Some(Jump::Gap);
+        }
+        ui.separator();
+        let mut labels = app
+            .project
+            .as_ref()
+            .unwrap()
+            .used_labels
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        labels.sort();
+        egui::ComboBox::from_id_salt("jump_label")
+            .selected_text(if state.jump_label.is_empty() {
+                "label"
+            } else {
+                &state.jump_label
+            })
+            .show_ui(ui, |ui| {
+                for label in labels {
+                    ui.selectable_value(&mut state.jump_label, label.clone(), label);
+                }
+            });
+        if ui.button("Previous (Shift+N)").clicked() {
+            jump = Some(Jump::Label { forward: false });
+        }
+        if ui.button("Next (N)").clicked() {
+            jump = Some(Jump::Label { forward: true });
+        }
+        ui.separator();
+        ui.label("Go to");
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut state.goto_input)
+                .hint_text("frame or h:mm:ss")
+                .desired_width(100.0),
+        );
+        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        if ui.button("Go").clicked() || submitted {
+            match parse_position(&state.goto_input, state.fps) {
+                Some(frame) => {
+                    state.goto_error = false;
+                    state.seek(frame);
+                }
+                None => state.goto_error = true,
+            }
+        }
+        if state.goto_error {
+            ui.colored_label(egui::Color32::RED, "not a frame or timecode");
+        }
+        if let Some(jump) = jump {
+            jump_to(app, state, jump);
+        }
+    });
+}
+
+fn shots_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
+    ui.horizontal(|ui| {
+        let project = app.project.as_ref().unwrap();
+        let cuts = project.shot_cuts.get(&state.video_name);
+        if let Some(progress) = app.shots.progress(&state.video_name) {
+            ui.add(
+                egui::ProgressBar::new(progress)
+                    .desired_width(160.0)
+                    .text("detecting cuts"),
+            );
+        } else {
+            if let Some(cuts) = cuts {
+                ui.label(format!("{} shot cuts", cuts.len()));
+            }
+            let text = if cuts.is_some() {
+                "Detect again"
+            } else {
+                "Detect shot cuts"
+            };
+            if ui.button(text).clicked() {
+                let path = project.video_path(&state.video_name);
+                app.shots
+                    .request(&[(state.video_name.clone(), path)], ui.ctx());
+            }
+        }
+        ui.checkbox(&mut state.snap_to_cuts, "Snap S/E to cuts");
+        let Some(cuts) = cuts.cloned() else {
+            return;
+        };
+        if ui
+            .button("Create segments between cuts")
+            .on_hover_text("Adds an unlabeled segment for every shot that has none yet")
+            .clicked()
+        {
+            let total_frames = state.capture.get(videoio::CAP_PROP_FRAME_COUNT).unwrap() as u32;
+            let author = app.settings.annotator();
+            let project = app.project.as_mut().unwrap();
+            let annotations = project
+                .annotations
+                .entry(state.video_name.clone())
+                .or_default();
+            let mut created = Vec::new();
+            for (start, end) in shots::shots(&cuts, total_frames) {
+                let exists = annotations.iter().any(|annotation| {
+                    annotation.start_frame == start && annotation.end_frame == end
+                });
+                if !exists {
+                    let annotation =
+                        FrameAnnotation::new(start, end, String::new()).with_author(author.clone());
+                    created.push(annotation.id.clone());
+                    annotations.push(annotation);
+                }
+            }
+            for id in created {
+                project.annotation_changed(&state.video_name, &id);
+            }
+        }
+    });
+}
+
+fn proposals_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
+    ui.horizontal(|ui| {
+        let project = app.project.as_ref().unwrap();
+        if let Some(progress) = app.prelabeler.progress(&state.video_name) {
+            ui.add(
+                egui::ProgressBar::new(progress)
+                    .desired_width(160.0)
+                    .text("pre-labeling"),
+            );
+        } else {
+            let enabled = project.model.is_some() && !app.prelabeler.is_running();
+            let response = ui
+                .add_enabled(enabled, egui::Button::new("Pre-label with model"))
+                .on_disabled_hover_text("Set up a model on the home screen first");
+            if response.clicked() {
+                if project.proposals.contains_key(&state.video_name) {
+                    state.show_prelabel_popup = true;
+                } else {
+                    start_prelabeling(ui.ctx(), state, app);
+                    return;
+                }
+            }
+        }
+        if let Some(error) = app.prelabeler.error(&state.video_name) {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        let count = project
+            .proposals
+            .get(&state.video_name)
+            .map_or(0, |proposals| proposals.len());
+        if count == 0 {
+            return;
+        }
+        ui.label(format!("{} proposals", count));
+        let ids = project.proposals[&state.video_name]
+            .iter()
+            .map(|proposal| proposal.id.clone())
+            .collect::<Vec<_>>();
+        let author = app.settings.annotator();
+        let project = app.project.as_mut().unwrap();
+        if ui.button("Accept all").clicked() {
+            for id in &ids {
+                project.accept_proposal(&state.video_name, id, author.clone());
+            }
+        }
+        if ui.button("Reject all").clicked() {
+            project.proposals.remove(&state.video_name);
+        }
+    });
+}
+
+fn start_prelabeling(ctx: &egui::Context, state: &LabelView, app: &mut GlobalState) {
+    let project = app.project.as_ref().unwrap();
+    app.prelabeler.start(
+        &state.video_name,
+        project.video_path(&state.video_name),
+        project.model_path().unwrap(),
+        project.model.clone().unwrap(),
+        ctx,
+    );
+}
+
+fn scores_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
+    let project = app.project.as_mut().unwrap();
+    let imported = project.scores_path(&state.video_name);
+    let computed = project.model_scores_path(&state.video_name);
+    let has_both = imported.exists() && computed.exists();
+    let path = if (state.model_scores && computed.exists()) || !imported.exists() {
+        computed
+    } else {
+        imported
+    };
+    let loaded = std::fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(|modified| (path.clone(), modified));
+    if loaded != state.scores_loaded {
+        state.scores_loaded = loaded;
+        state.scores_error = None;
+        match FrameScores::read(&path) {
+            Ok(scores) => state.scores = scores,
+            Err(e) => state.scores_error = Some(e),
+        }
+        // the new file may have fewer classes
+        let classes = state
+            .scores
+            .as_ref()
+            .map_or(0, |scores| scores.classes.len());
+        state.score_class = state.score_class.min(classes.saturating_sub(1));
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Import scores").clicked() {
+            if let Some(file) = FileDialog::new().add_filter("CSV", &["csv"]).pick_file() {
+                match project.import_scores(&state.video_name, &file) {
+                    Ok(_) => {
+                        // read again below on the next frame
+                        state.scores_loaded = None;
+                        state.model_scores = false;
+                        state.score_class = 0;
+                    }
+                    Err(e) => state.scores_error = Some(format!("Failed to import scores: {}", e)),
+                }
+            }
+        }
+        if has_both {
+            ui.selectable_value(&mut state.model_scores, false, "Imported");
+            ui.selectable_value(&mut state.model_scores, true, "Model");
+        }
+        if let Some(error) = &state.scores_error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        let Some(scores) = &state.scores else {
+            return;
+        };
+        ui.checkbox(&mut state.score_lines, "Lines");
+        ui.separator();
+        egui::ComboBox::from_id_salt("score_class")
+            .selected_text(
+                scores
+                    .classes
+                    .get(state.score_class)
+                    .map_or("", String::as_str),
+            )
+            .show_ui(ui, |ui| {
+                for (index, class) in scores.classes.iter().enumerate() {
+                    ui.selectable_value(&mut state.score_class, index, class);
+                }
+            });
+        ui.label("threshold");
+        ui.add(egui::Slider::new(&mut state.score_threshold, 0.0..=1.0));
+        ui.label("min length");
+        ui.add(egui::DragValue::new(&mut state.score_min_length).suffix(" frames"));
+        if ui
+            .button("Propose segments")
+            .on_hover_text("Adds the stretches above the threshold as proposals")
+            .clicked()
+        {
+            let Some(label) = scores.classes.get(state.score_class).cloned() else {
+                return;
+            };
+            let proposals = project
+                .proposals
+                .entry(state.video_name.clone())
+                .or_default();
+            for (start, end, mean) in scores.candidates(
+                state.score_class,
+                state.score_threshold,
+                state.score_min_length,
+            ) {
+                let exists = proposals.iter().any(|proposal| {
+                    proposal.label == label
+                        && proposal.start_frame == start
+                        && proposal.end_frame == end
+                });
+                if !exists {
+                    let mut proposal = FrameAnnotation::new(start, end, label.clone());
+                    proposal.confidence = Some(mean);
+                    proposals.push(proposal);
+                }
+            }
+            if proposals.is_empty() {
+                project.proposals.remove(&state.video_name);
+            }
+        }
+    });
+}
+
+/// Searches the indexed frames of all videos for ones that look like the current frame.
+fn similar_ui(
+    ui: &mut egui::Ui,
+    app: &mut GlobalState,
+    state: &mut LabelView,
+) -> Option<Box<dyn View>> {
+    let mut next_view = None;
+    ui.horizontal(|ui| {
+        let (indexed, remaining) = app.frame_index.counts();
+        if let Some((video, progress)) = app.frame_index.progress() {
+            ui.add(
+                egui::ProgressBar::new(progress)
+                    .desired_width(160.0)
+                    .text(format!("indexing {} ({} left)", video, remaining)),
+            );
+        }
+        ui.label(format!("{} videos indexed", indexed));
+        if ui
+            .button("Find similar frames")
+            .on_hover_text("Indexes the videos not indexed yet and searches the indexed ones")
+            .clicked()
+        {
+            let project = app.project.as_ref().unwrap();
+            let videos = project
+                .videos()
+                .into_iter()
+                .map(|(video, path)| {
+                    let features_path = project.features_path(&video);
+                    (video, path, features_path)
+                })
+                .collect::<Vec<_>>();
+            app.frame_index.request(&videos, ui.ctx());
+            let frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+            match state.current_frame.as_ref().map(frame_features) {
+                Some(Ok(query)) => {
+                    let paths = videos
+                        .into_iter()
+                        .map(|(video, path, _)| (video, path))
+                        .collect();
+                    app.similar_frames = Some(SimilarFrames::new(
+                        state.video_name.clone(),
+                        frame,
+                        query,
+                        paths,
+                        &app.frame_index,
+                        ui.ctx(),
+                    ));
+                }
+                Some(Err(e)) => eprintln!("Failed to compute features of frame {}: {}", frame, e),
+                None => {}
+            }
+        }
+        if app.similar_frames.is_some() && ui.button("Clear").clicked() {
+            app.similar_frames = None;
+        }
+    });
+    let similar = app.similar_frames.as_mut()?;
+    similar.update(&app.frame_index, ui.ctx());
+    // the matches come from other videos, whose frame rates are read by the library
+    let library = &mut app.library;
+    let project = app.project.as_ref().unwrap();
+    let ctx = ui.ctx().clone();
+    let mut fps = |video: &str| {
+        if video == state.video_name {
+            return state.fps;
+        }
+        library.request(&[(video.to_string(), project.video_path(video))], &ctx);
+        library.info(video).map_or(0.0, |info| info.fps)
+    };
+    ui.label(format!(
+        "Frames like {} at {}",
+        similar.video,
+        format_frame(similar.frame, fps(&similar.video))
+    ));
+    let Some(matches) = similar.matches() else {
+        ui.spinner();
+        return None;
+    };
+    if matches.is_empty() {
+        ui.label("No indexed frames yet, the search runs again as videos get indexed.");
+        return None;
+    }
+    let mut opened = None;
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        ui.horizontal(|ui| {
+            for found in &matches {
+                ui.vertical(|ui| {
+                    let clicked = match similar.thumbnail(found) {
+                        Some(thumbnail) => ui
+                            .add(egui::ImageButton::new((
+                                thumbnail.id(),
+                                thumbnail.size_vec2(),
+                            )))
+                            .clicked(),
+                        None => ui
+                            .add_sized(
+                                [THUMBNAIL_WIDTH as f32, THUMBNAIL_WIDTH as f32 * 9.0 / 16.0],
+                                egui::Button::new("..."),
+                            )
+                            .clicked(),
+                    };
+                    if clicked {
+                        opened = Some(found.clone());
+                    }
+                    ui.small(&found.video);
+                    ui.small(format!(
+                        "{} ({:.2})",
+                        format_frame(found.frame, fps(&found.video)),
+                        found.similarity
+                    ));
+                });
             }
         });
+    });
+    let found = opened?;
+    if found.video == state.video_name {
+        state.seek(found.frame);
+    } else {
+        let project = app.project.as_ref().unwrap();
+        let path = project.video_path(&found.video);
+        let mut view = LabelView::open(project, found.video, path);
+        view.seek(found.frame);
+        app.annotator_filter = None;
+        next_view = Some(Box::new(view) as Box<dyn View>);
     }
     next_view
 }
 
+fn jump_to(app: &GlobalState, state: &mut LabelView, jump: Jump) {
+    let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+    let total_frames = state.capture.get(videoio::CAP_PROP_FRAME_COUNT).unwrap() as u32;
+    let annotations = app
+        .project
+        .as_ref()
+        .unwrap()
+        .annotations
+        .get(&state.video_name)
+        .into_iter()
+        .flatten()
+        .filter(|annotation| app.matches_annotator_filter(annotation))
+        .collect::<Vec<_>>();
+
+    let target = match jump {
+        Jump::Boundary { forward } => {
+            let boundaries = annotations
+                .iter()
+                .flat_map(|annotation| [annotation.start_frame, annotation.end_frame]);
+            if forward {
+                boundaries.filter(|frame| *frame > current_frame).min()
+            } else {
+                boundaries.filter(|frame| *frame < current_frame).max()
+            }
+        }
+        Jump::Label { forward } => {
+            let starts = annotations
+                .iter()
+                .filter(|annotation| annotation.label == state.jump_label)
+                .map(|annotation| annotation.start_frame);
+            if forward {
+                starts.filter(|frame| *frame > current_frame).min()
+            } else {
+                starts.filter(|frame| *frame < current_frame).max()
+            }
+        }
+        Jump::Gap => {
+            // skip over everything covering the candidate until nothing does
+            let mut frame = Some(current_frame.saturating_add(1));
+            while let Some(end) = frame.and_then(|frame| {
+                annotations
+                    .iter()
+                    .filter(|annotation| annotation.contains(frame))
+                    .map(|annotation| match annotation.kind {
+                        AnnotationKind::Interval => annotation.end_frame,
+                        AnnotationKind::Event { tolerance } => {
+                            annotation.start_frame.saturating_add(tolerance)
+                        }
+                    })
+                    .max()
+            }) {
+                // nothing is left after an annotation that ends on the last possible frame
+                frame = end.checked_add(1);
+            }
+            frame.filter(|frame| *frame < total_frames)
+        }
+    };
+    if let Some(frame) = target {
+        state.is_playing = false;
+        state.seek(frame);
+    }
+}
+
 pub fn playback_ui(ui: &mut egui::Ui, state: &mut LabelView) -> Option<Box<dyn View>> {
     let mut next_view = None;
     ui.horizontal(|ui| {
@@ -152,7 +871,195 @@ pub fn seeker_ui(ui: &mut egui::Ui, state: &mut LabelView) {
     }
 }
 
-pub fn video_ui(ui: &mut egui::Ui, _app: &mut GlobalState, state: &mut LabelView) {
+fn timeline_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
+    let total_frames = state
+        .capture
+        .get(videoio::CAP_PROP_FRAME_COUNT)
+        .unwrap()
+        .max(1.0) as f32;
+    let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as f32;
+
+    ui.horizontal(|ui| {
+        ui.label("Timeline zoom");
+        ui.add(egui::Slider::new(&mut state.timeline_zoom, 1.0..=64.0).logarithmic(true));
+    });
+    // the visible part of the timeline follows the current frame when zoomed in
+    let visible_frames = total_frames / state.timeline_zoom.max(1.0);
+    let first_visible =
+        (current_frame - visible_frames / 2.0).clamp(0.0, total_frames - visible_frames);
+
+    let (response, painter) =
+        ui.allocate_painter(egui::vec2(ui.available_width(), 24.0), egui::Sense::click());
+    let rect = response.rect;
+    let painter = painter.with_clip_rect(rect);
+    let x = |frame: f32| rect.left() + rect.width() * (frame - first_visible) / visible_frames;
+
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let annotations = app
+        .project
+        .as_ref()
+        .unwrap()
+        .annotations
+        .get(&state.video_name);
+    for annotation in annotations
+        .into_iter()
+        .flatten()
+        .filter(|annotation| app.matches_annotator_filter(annotation))
+    {
+        let color = label_color(&annotation.label);
+        let start = annotation.start_frame as f32;
+        match annotation.kind {
+            AnnotationKind::Interval => {
+                let end = annotation.end_frame as f32 + 1.0;
+                painter.rect_filled(
+                    egui::Rect::from_x_y_ranges(
+                        x(start)..=x(end).max(x(start) + 1.0),
+                        rect.y_range(),
+                    ),
+                    0.0,
+                    color.gamma_multiply(0.6),
+                );
+            }
+            AnnotationKind::Event { tolerance } => {
+                let tolerance = tolerance as f32;
+                painter.rect_filled(
+                    egui::Rect::from_x_y_ranges(
+                        x(start - tolerance)..=x(start + tolerance + 1.0),
+                        rect.y_range(),
+                    ),
+                    0.0,
+                    color.gamma_multiply(0.25),
+                );
+                painter.line_segment(
+                    [
+                        egui::pos2(x(start), rect.top()),
+                        egui::pos2(x(start), rect.bottom()),
+                    ],
+                    egui::Stroke::new(2.0, color),
+                );
+                painter.circle_filled(egui::pos2(x(start), rect.top() + 4.0), 4.0, color);
+            }
+        }
+    }
+    // proposals are outlined in the lower half so they don't hide the annotations
+    let proposals = app
+        .project
+        .as_ref()
+        .unwrap()
+        .proposals
+        .get(&state.video_name);
+    for proposal in proposals.into_iter().flatten() {
+        let start = proposal.start_frame as f32;
+        let end = proposal.end_frame as f32 + 1.0;
+        painter.rect_stroke(
+            egui::Rect::from_x_y_ranges(
+                x(start)..=x(end).max(x(start) + 1.0),
+                rect.center().y..=rect.bottom(),
+            ),
+            0.0,
+            egui::Stroke::new(1.5, label_color(&proposal.label)),
+        );
+    }
+    if let Some((start, end)) = state.focus_region {
+        painter.rect_stroke(
+            egui::Rect::from_x_y_ranges(
+                x(start as f32)..=x(end as f32 + 1.0).max(x(start as f32) + 1.0),
+                rect.y_range(),
+            ),
+            0.0,
+            egui::Stroke::new(2.0, egui::Color32::YELLOW),
+        );
+    }
+    let cuts = app
+        .project
+        .as_ref()
+        .unwrap()
+        .shot_cuts
+        .get(&state.video_name);
+    for cut in cuts.into_iter().flatten() {
+        let cut = *cut as f32;
+        painter.line_segment(
+            [
+                egui::pos2(x(cut), rect.bottom() - 8.0),
+                egui::pos2(x(cut), rect.bottom()),
+            ],
+            egui::Stroke::new(1.0, ui.visuals().strong_text_color()),
+        );
+    }
+    painter.line_segment(
+        [
+            egui::pos2(x(current_frame), rect.top()),
+            egui::pos2(x(current_frame), rect.bottom()),
+        ],
+        egui::Stroke::new(1.5, egui::Color32::RED),
+    );
+
+    if response.clicked() {
+        if let Some(position) = response.interact_pointer_pos() {
+            let frame = first_visible + (position.x - rect.left()) / rect.width() * visible_frames;
+            state.seek(frame.max(0.0) as u32);
+        }
+    }
+
+    // one lane per class of the imported scores, aligned with the timeline above
+    let Some(scores) = &state.scores else {
+        return;
+    };
+    for (class, name) in scores.classes.iter().enumerate() {
+        let (response, painter) =
+            ui.allocate_painter(egui::vec2(ui.available_width(), 16.0), egui::Sense::hover());
+        let rect = response.rect;
+        let painter = painter.with_clip_rect(rect);
+        let color = label_color(name);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+        // sampling per pixel column keeps long videos cheap to draw
+        let columns = rect.width().max(1.0) as usize;
+        let samples = (0..columns)
+            .map(|column| {
+                let frame = first_visible + column as f32 / columns as f32 * visible_frames;
+                scores.at(class, frame as u32)
+            })
+            .collect::<Vec<_>>();
+        if state.score_lines {
+            let y = |score: f32| rect.bottom() - score.clamp(0.0, 1.0) * rect.height();
+            let points = samples
+                .iter()
+                .enumerate()
+                .filter_map(|(column, score)| {
+                    Some(egui::pos2(rect.left() + column as f32, y((*score)?)))
+                })
+                .collect::<Vec<_>>();
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+            if class == state.score_class {
+                painter.hline(
+                    rect.x_range(),
+                    y(state.score_threshold),
+                    egui::Stroke::new(1.0, ui.visuals().weak_text_color()),
+                );
+            }
+        } else {
+            for (column, score) in samples.iter().enumerate() {
+                if let Some(score) = score {
+                    let left = rect.left() + column as f32;
+                    painter.rect_filled(
+                        egui::Rect::from_x_y_ranges(left..=left + 1.0, rect.y_range()),
+                        0.0,
+                        color.gamma_multiply(score.clamp(0.0, 1.0)),
+                    );
+                }
+            }
+        }
+        painter.text(
+            rect.left_center() + egui::vec2(4.0, 0.0),
+            egui::Align2::LEFT_CENTER,
+            name,
+            egui::FontId::proportional(11.0),
+            ui.visuals().strong_text_color(),
+        );
+    }
+}
+
+pub fn video_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
     if state.current_frame.is_none() {
         state.advance_frame(1);
     }
@@ -194,7 +1101,110 @@ pub fn video_ui(ui: &mut egui::Ui, _app: &mut GlobalState, state: &mut LabelView
         egui::TextureOptions::default(),
     );
 
-    ui.image(&texture);
+    let response = ui.add(egui::Image::new(&texture).sense(egui::Sense::drag()));
+    boxes_ui(ui, &response, app, state);
+}
+
+/// Draws the boxes of all segments at the current frame and lets the user drag a new
+/// keyframe box for the selected segment.
+fn boxes_ui(
+    ui: &mut egui::Ui,
+    response: &egui::Response,
+    app: &mut GlobalState,
+    state: &mut LabelView,
+) {
+    let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+    let rect = response.rect;
+    let to_screen = |bbox: &BoundingBox| {
+        egui::Rect::from_min_size(
+            egui::pos2(
+                rect.left() + bbox.x * rect.width(),
+                rect.top() + bbox.y * rect.height(),
+            ),
+            egui::vec2(bbox.width * rect.width(), bbox.height * rect.height()),
+        )
+    };
+
+    let annotations = app
+        .project
+        .as_ref()
+        .unwrap()
+        .annotations
+        .get(&state.video_name)
+        .map_or(&[][..], Vec::as_slice);
+    let painter = ui.painter_at(rect);
+    for annotation in annotations
+        .iter()
+        .filter(|annotation| app.matches_annotator_filter(annotation))
+    {
+        if let Some(bbox) = annotation.box_at(current_frame) {
+            let is_keyframe = annotation
+                .keyframes
+                .iter()
+                .any(|keyframe| keyframe.frame == current_frame);
+            let width = if state.selected_annotation.as_ref() == Some(&annotation.id) {
+                3.0
+            } else {
+                1.5
+            };
+            let color = label_color(&annotation.label);
+            let stroke = if is_keyframe {
+                egui::Stroke::new(width, color)
+            } else {
+                egui::Stroke::new(width, color.gamma_multiply(0.7))
+            };
+            let screen_rect = to_screen(&bbox);
+            painter.rect_stroke(screen_rect, 0.0, stroke);
+            painter.text(
+                screen_rect.left_top(),
+                egui::Align2::LEFT_BOTTOM,
+                &annotation.label,
+                egui::FontId::proportional(12.0),
+                color,
+            );
+        }
+    }
+
+    let selected = state.selected_annotation.as_ref().and_then(|id| {
+        annotations
+            .iter()
+            .position(|annotation| &annotation.id == id && annotation.contains(current_frame))
+    });
+    let Some(selected) = selected else {
+        state.box_drag_start = None;
+        return;
+    };
+
+    if response.drag_started() {
+        state.box_drag_start = response.interact_pointer_pos();
+    }
+    let Some(start) = state.box_drag_start else {
+        return;
+    };
+    let Some(end) = response.interact_pointer_pos().or(response.hover_pos()) else {
+        return;
+    };
+    let drag_rect = egui::Rect::from_two_pos(start, end).intersect(rect);
+    painter.rect_stroke(drag_rect, 0.0, egui::Stroke::new(2.0, egui::Color32::WHITE));
+
+    if response.drag_stopped() {
+        state.box_drag_start = None;
+        if drag_rect.width() < 2.0 || drag_rect.height() < 2.0 {
+            return;
+        }
+        let bbox = BoundingBox {
+            x: (drag_rect.left() - rect.left()) / rect.width(),
+            y: (drag_rect.top() - rect.top()) / rect.height(),
+            width: drag_rect.width() / rect.width(),
+            height: drag_rect.height() / rect.height(),
+        };
+        let project = app.project.as_mut().unwrap();
+        let annotation = &mut project.annotations.get_mut(&state.video_name).unwrap()[selected];
+        annotation.set_keyframe(current_frame, bbox);
+        annotation.touch();
+        let id = annotation.id.clone();
+        project.annotation_changed(&state.video_name, &id);
+    }
 }
 
 fn label_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
@@ -204,30 +1214,470 @@ fn label_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
         ui.label(&format!(
             "Labels: {}",
             app.project
-                .as_mut()
+                .as_ref()
                 .unwrap()
                 .annotations
-                .entry(state.video_name.clone())
-                .or_insert(vec![])
-                .iter()
+                .get(&state.video_name)
+                .into_iter()
+                .flatten()
                 .filter(|annotation| annotation.contains(current_frame as u32))
+                .filter(|annotation| app.matches_annotator_filter(annotation))
                 .map(|annotation| annotation.label.clone())
                 .collect::<Vec<String>>()
                 .join(", ")
         ));
     });
-    ui.label(&format!(
-        "start: {}",
-        state
-            .current_start_frame
-            .map_or("-".to_string(), |start| start.to_string())
-    ));
-    ui.label(&format!(
-        "end: {}",
-        state
-            .current_end_frame
-            .map_or("-".to_string(), |end| end.to_string())
-    ));
+    ui.horizontal(|ui| {
+        let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+        // the timestamp the video reports for the current frame, which also holds for
+        // videos with a variable frame rate
+        let position = state.capture.get(videoio::CAP_PROP_POS_MSEC).unwrap_or(0.0) / 1000.0;
+        ui.label(format!(
+            "frame {} at {}",
+            current_frame,
+            format_timecode(position)
+        ));
+    });
+    let fps = state.fps;
+    let last_frame = (state
+        .capture
+        .get(videoio::CAP_PROP_FRAME_COUNT)
+        .unwrap_or(0.0) as u32)
+        .saturating_sub(1);
+    let (start, end) = (state.current_start_frame, state.current_end_frame);
+    for (name, mark, input) in [
+        (
+            "start",
+            &mut state.current_start_frame,
+            &mut state.start_input,
+        ),
+        ("end", &mut state.current_end_frame, &mut state.end_input),
+    ] {
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{}: {}",
+                name,
+                mark.map_or("-".to_string(), |frame| format_frame(frame, fps))
+            ));
+            ui.add(
+                egui::TextEdit::singleline(input)
+                    .hint_text("frame or h:mm:ss.mmm")
+                    .desired_width(110.0),
+            );
+            let frame = parse_position(input, fps).map(|frame| frame.min(last_frame));
+            // like the S and E keys, a mark can't be set on the wrong side of the other one
+            let inverted = frame.map_or(false, |frame| match name {
+                "start" => end.map_or(false, |end| frame > end),
+                _ => start.map_or(false, |start| frame < start),
+            });
+            if ui
+                .add_enabled(
+                    frame.is_some() && !inverted,
+                    egui::Button::new(format!("Set {}", name)),
+                )
+                .clicked()
+            {
+                *mark = frame;
+                input.clear();
+            }
+            if inverted {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    match name {
+                        "start" => "after the end",
+                        _ => "before the start",
+                    },
+                );
+            }
+        });
+    }
+    if let (Some(start), Some(end)) = (state.current_start_frame, state.current_end_frame) {
+        if start > end {
+            ui.colored_label(egui::Color32::RED, "start is after end");
+        }
+    }
+}
+
+fn annotation_panel(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
+    ui.heading("Annotations");
+    proposals_panel(ui, app, state);
+    let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+    // the filter is applied up front, the annotations are edited below
+    let shown = app
+        .project
+        .as_ref()
+        .unwrap()
+        .annotations
+        .get(&state.video_name)
+        .into_iter()
+        .flatten()
+        .filter(|annotation| {
+            annotation.contains(current_frame) && app.matches_annotator_filter(annotation)
+        })
+        .map(|annotation| annotation.id.clone())
+        .collect::<HashSet<_>>();
+    let project = app.project.as_mut().unwrap();
+    let definitions = project.label_attributes.clone();
+    let annotations = project
+        .annotations
+        .entry(state.video_name.clone())
+        .or_default();
+
+    let mut to_delete = None;
+    let mut changed_ids = Vec::new();
+    let mut new_labels = Vec::new();
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for annotation in annotations
+            .iter_mut()
+            .filter(|annotation| shown.contains(&annotation.id))
+        {
+            let label = if annotation.label.is_empty() {
+                "(unlabeled)"
+            } else {
+                &annotation.label
+            };
+            let title = if annotation.is_event() {
+                format!(
+                    "{}: @{}",
+                    label,
+                    format_frame(annotation.start_frame, state.fps)
+                )
+            } else {
+                format!(
+                    "{}: {} - {}",
+                    label,
+                    format_frame(annotation.start_frame, state.fps),
+                    format_frame(annotation.end_frame, state.fps)
+                )
+            };
+            egui::CollapsingHeader::new(title)
+                .id_salt(&annotation.id)
+                .default_open(true)
+                .show(ui, |ui| {
+                    if let Some(author) = &annotation.author {
+                        ui.label(format!("by {}", author));
+                    }
+                    if let Some(created_at) = annotation.created_at {
+                        ui.label(format!("created {}", format_timestamp(created_at)));
+                    }
+                    if let Some(modified_at) = annotation.modified_at {
+                        ui.label(format!("modified {}", format_timestamp(modified_at)));
+                    }
+                    let mut changed = false;
+                    if !annotation.is_labeled() {
+                        ui.horizontal(|ui| {
+                            let input = state
+                                .segment_label_inputs
+                                .entry(annotation.id.clone())
+                                .or_default();
+                            ui.text_edit_singleline(input);
+                            if ui.button("Set label").clicked() && !input.is_empty() {
+                                annotation.label = std::mem::take(input);
+                                new_labels.push(annotation.label.clone());
+                                changed = true;
+                            }
+                        });
+                    }
+                    if !annotation.is_event() {
+                        changed |= keyframes_ui(
+                            ui,
+                            annotation,
+                            current_frame,
+                            &mut state.selected_annotation,
+                        );
+                    }
+                    let definitions = definitions
+                        .get(&annotation.label)
+                        .map_or(&[][..], |definitions| definitions.as_slice());
+                    changed |= annotation_editor(ui, annotation, definitions);
+                    if changed {
+                        annotation.touch();
+                        changed_ids.push(annotation.id.clone());
+                    }
+                    if ui.button("Delete").clicked() {
+                        to_delete = Some(annotation.id.clone());
+                    }
+                });
+        }
+    });
+    for id in changed_ids {
+        project.annotation_changed(&state.video_name, &id);
+    }
+    project.used_labels.extend(new_labels);
+    if let Some(id) = to_delete {
+        project.remove_annotation(&state.video_name, &id);
+    }
+}
+
+/// Proposals at the current frame, which can be adjusted before they are accepted.
+fn proposals_panel(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
+    let current_frame = state.capture.get(videoio::CAP_PROP_POS_FRAMES).unwrap() as u32;
+    let last_frame = (state
+        .capture
+        .get(videoio::CAP_PROP_FRAME_COUNT)
+        .unwrap_or(0.0) as u32)
+        .saturating_sub(1);
+    let author = app.settings.annotator();
+    let project = app.project.as_mut().unwrap();
+    let Some(proposals) = project.proposals.get_mut(&state.video_name) else {
+        return;
+    };
+    if !proposals
+        .iter()
+        .any(|proposal| proposal.contains(current_frame))
+    {
+        return;
+    }
+    let mut accepted = None;
+    let mut rejected = None;
+    ui.label("Proposals");
+    for proposal in proposals
+        .iter_mut()
+        .filter(|proposal| proposal.contains(current_frame))
+    {
+        let title = format!(
+            "{}: {} - {} ({:.0}%)",
+            proposal.label,
+            format_frame(proposal.start_frame, state.fps),
+            format_frame(proposal.end_frame, state.fps),
+            proposal.confidence.unwrap_or(0.0) * 100.0
+        );
+        egui::CollapsingHeader::new(title)
+            .id_salt(&proposal.id)
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Label");
+                    ui.text_edit_singleline(&mut proposal.label);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Frames");
+                    let end_frame = proposal.end_frame;
+                    ui.add(egui::DragValue::new(&mut proposal.start_frame).range(0..=end_frame));
+                    let start_frame = proposal.start_frame;
+                    ui.add(
+                        egui::DragValue::new(&mut proposal.end_frame)
+                            .range(start_frame..=last_frame.max(start_frame)),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!proposal.label.is_empty(), egui::Button::new("Accept"))
+                        .clicked()
+                    {
+                        accepted = Some(proposal.id.clone());
+                    }
+                    if ui.button("Reject").clicked() {
+                        rejected = Some(proposal.id.clone());
+                    }
+                });
+            });
+    }
+    if let Some(id) = accepted {
+        project.accept_proposal(&state.video_name, &id, author);
+    }
+    if let Some(id) = rejected {
+        project.reject_proposal(&state.video_name, &id);
+    }
+    ui.separator();
+}
+
+fn keyframes_ui(
+    ui: &mut egui::Ui,
+    annotation: &mut FrameAnnotation,
+    current_frame: u32,
+    selected_annotation: &mut Option<String>,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        let mut selected = selected_annotation.as_ref() == Some(&annotation.id);
+        if ui.checkbox(&mut selected, "Draw boxes").changed() {
+            *selected_annotation = if selected {
+                Some(annotation.id.clone())
+            } else {
+                None
+            };
+        }
+        ui.label(format!("{} keyframes", annotation.keyframes.len()));
+        let is_keyframe = annotation
+            .keyframes
+            .iter()
+            .any(|keyframe| keyframe.frame == current_frame);
+        if is_keyframe && ui.button("Remove keyframe").clicked() {
+            annotation
+                .keyframes
+                .retain(|keyframe| keyframe.frame != current_frame);
+            changed = true;
+        }
+    });
+    changed
+}
+
+fn annotation_editor(
+    ui: &mut egui::Ui,
+    annotation: &mut FrameAnnotation,
+    definitions: &[AttributeDefinition],
+) -> bool {
+    let mut changed = false;
+    if let AnnotationKind::Event { tolerance } = &mut annotation.kind {
+        ui.horizontal(|ui| {
+            ui.label("Tolerance");
+            changed |= ui
+                .add(egui::DragValue::new(tolerance).suffix(" frames"))
+                .changed();
+        });
+    }
+    ui.label("Note");
+    changed |= ui.text_edit_multiline(&mut annotation.note).changed();
+
+    ui.horizontal(|ui| {
+        let mut has_confidence = annotation.confidence.is_some();
+        if ui.checkbox(&mut has_confidence, "Confidence").changed() {
+            annotation.confidence = if has_confidence { Some(1.0) } else { None };
+            changed = true;
+        }
+        if let Some(confidence) = annotation.confidence.as_mut() {
+            changed |= ui.add(egui::Slider::new(confidence, 0.0..=1.0)).changed();
+        }
+    });
+
+    for definition in definitions {
+        let mut value = annotation
+            .attributes
+            .get(&definition.name)
+            .cloned()
+            .unwrap_or_else(|| definition.default_value());
+        let value_changed = ui
+            .horizontal(|ui| {
+                ui.label(&definition.name);
+                attribute_input(ui, &annotation.id, definition, &mut value)
+            })
+            .inner;
+        if value_changed {
+            annotation.attributes.insert(definition.name.clone(), value);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// `id` is the annotation's, so that the inputs of several annotations don't clash.
+fn attribute_input(
+    ui: &mut egui::Ui,
+    id: &str,
+    definition: &AttributeDefinition,
+    value: &mut AttributeValue,
+) -> bool {
+    match (&definition.kind, value) {
+        (AttributeKind::Bool, AttributeValue::Bool(value)) => ui.checkbox(value, "").changed(),
+        (AttributeKind::Enum(options), AttributeValue::Text(value)) => {
+            let mut changed = false;
+            egui::ComboBox::from_id_salt((id, &definition.name))
+                .selected_text(value.as_str())
+                .show_ui(ui, |ui| {
+                    for option in options {
+                        changed |= ui.selectable_value(value, option.clone(), option).changed();
+                    }
+                });
+            changed
+        }
+        (AttributeKind::Number, AttributeValue::Number(value)) => {
+            ui.add(egui::DragValue::new(value).speed(0.1)).changed()
+        }
+        (AttributeKind::Text, AttributeValue::Text(value)) => {
+            ui.text_edit_singleline(value).changed()
+        }
+        (_, value) => {
+            // the stored value does not match the definition anymore
+            *value = definition.default_value();
+            true
+        }
+    }
+}
+
+fn review_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut LabelView) {
+    let project = app.project.as_mut().unwrap();
+    let status = project.status(&state.video_name);
+    status_badge(ui, &status);
+    match status {
+        VideoStatus::Unlabeled | VideoStatus::InProgress | VideoStatus::Rejected { .. } => {
+            if ui.button("Submit").clicked() {
+                project.set_status(&state.video_name, VideoStatus::Submitted);
+            }
+        }
+        VideoStatus::Submitted => {
+            if ui.button("Approve").clicked() {
+                project.set_status(&state.video_name, VideoStatus::Approved);
+            }
+            if ui.button("Reject").clicked() {
+                state.show_reject_popup = true;
+            }
+        }
+        VideoStatus::Approved => {
+            if ui.button("Reopen").clicked() {
+                project.set_status(&state.video_name, VideoStatus::InProgress);
+            }
+        }
+    }
+}
+
+fn reject_popup(ctx: &egui::Context, state: &mut LabelView, app: &mut GlobalState) {
+    if !state.show_reject_popup {
+        return;
+    }
+    egui::Window::new("Reject")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("Comment");
+            ui.text_edit_multiline(&mut state.reject_comment);
+            ui.horizontal(|ui| {
+                if ui.button("Reject").clicked() {
+                    app.project.as_mut().unwrap().set_status(
+                        &state.video_name,
+                        VideoStatus::Rejected {
+                            comment: state.reject_comment.clone(),
+                        },
+                    );
+                    state.reject_comment.clear();
+                    state.show_reject_popup = false;
+                }
+                if ui.button("Cancel").clicked() {
+                    state.show_reject_popup = false;
+                }
+            });
+        });
+}
+
+fn prelabel_popup(ctx: &egui::Context, state: &mut LabelView, app: &mut GlobalState) {
+    if !state.show_prelabel_popup {
+        return;
+    }
+    let count = app
+        .project
+        .as_ref()
+        .unwrap()
+        .proposals
+        .get(&state.video_name)
+        .map_or(0, |proposals| proposals.len());
+    egui::Window::new("Pre-label again")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "The new run replaces the {} pending proposals of this video, including the ones you edited.",
+                count
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Replace proposals").clicked() {
+                    start_prelabeling(ctx, state, app);
+                    state.show_prelabel_popup = false;
+                }
+                if ui.button("Cancel").clicked() {
+                    state.show_prelabel_popup = false;
+                }
+            });
+        });
 }
 
 fn label_popup(
@@ -241,6 +1691,12 @@ fn label_popup(
             .collapsible(false)
             .resizable(false)
             .show(ctx, |ui| {
+                if let Some(frame) = state.current_event_frame {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Event at frame {}, tolerance", frame));
+                        ui.add(egui::DragValue::new(&mut state.event_tolerance).suffix(" frames"));
+                    });
+                }
                 let mut suggestions = app
                     .project
                     .as_ref()
@@ -271,6 +1727,7 @@ fn label_popup(
                     }
                     if ui.button("Cancel").clicked() {
                         state.show_label_popup = false;
+                        state.current_event_frame = None;
                         state.label_input.clear();
                     }
                 });
@@ -278,6 +1735,7 @@ fn label_popup(
         ctx.input(|i| {
             if i.key_pressed(egui::Key::Escape) {
                 state.show_label_popup = false;
+                state.current_event_frame = None;
                 state.label_input.clear();
             } else if i.key_pressed(egui::Key::Enter) {
                 close_label_popup(state, app);
@@ -287,24 +1745,28 @@ fn label_popup(
 }
 
 fn close_label_popup(state: &mut LabelView, app: &mut GlobalState) {
-    if state.current_end_frame.is_none() {
-        return;
-    }
-    if state.current_start_frame.is_none() {
-        return;
-    }
     if state.label_input.is_empty() {
         return;
     }
 
-    let start_frame = state.current_start_frame.take().unwrap();
-    let end_frame = state.current_end_frame.take().unwrap();
-    let annotation = FrameAnnotation {
-        start_frame,
-        end_frame,
-        label: state.label_input.clone(),
+    let annotation = if let Some(frame) = state.current_event_frame.take() {
+        FrameAnnotation::event(frame, state.event_tolerance, state.label_input.clone())
+            .with_author(app.settings.annotator())
+    } else {
+        if state.current_end_frame.is_none() {
+            return;
+        }
+        if state.current_start_frame.is_none() {
+            return;
+        }
+
+        let start_frame = state.current_start_frame.take().unwrap();
+        let end_frame = state.current_end_frame.take().unwrap();
+        FrameAnnotation::new(start_frame, end_frame, state.label_input.clone())
+            .with_author(app.settings.annotator())
     };
 
+    let id = annotation.id.clone();
     app.project
         .as_mut()
         .unwrap()
@@ -312,12 +1774,22 @@ fn close_label_popup(state: &mut LabelView, app: &mut GlobalState) {
         .entry(state.video_name.clone())
         .or_insert(vec![])
         .push(annotation);
+    app.project
+        .as_mut()
+        .unwrap()
+        .annotation_changed(&state.video_name, &id);
 
     app.project
         .as_mut()
         .unwrap()
         .used_labels
         .insert(state.label_input.clone());
+    if app.project.as_ref().unwrap().status(&state.video_name) == VideoStatus::Unlabeled {
+        app.project
+            .as_mut()
+            .unwrap()
+            .set_status(&state.video_name, VideoStatus::InProgress);
+    }
     state.label_input.clear();
     state.show_label_popup = false;
 }
@@ -336,6 +1808,14 @@ impl LabelView {
         }
     }
 
+    /// Moves the capture so that `frame` is the current frame position.
+    pub fn seek(&mut self, frame: u32) {
+        self.capture
+            .set(videoio::CAP_PROP_POS_FRAMES, frame.saturating_sub(1) as f64)
+            .unwrap();
+        self.advance_frame(1);
+    }
+
     pub fn previous_frame(&mut self, step: u32) {
         if let Ok(pos) = self.capture.get(videoio::CAP_PROP_POS_FRAMES) {
             self.capture
@@ -1,23 +1,26 @@
 use std::{
     collections::HashMap,
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::exit,
     sync::{Arc, Mutex},
     thread,
 };
 
 use crate::{
-    app::{FrameAnnotation, GlobalState},
-    utils::load_video,
+    app::{AnnotationKind, FrameAnnotation, GlobalState},
+    project::{AttributeDefinition, Label},
+    utils::{csv_field, format_frame, format_timecode, frame_seconds, load_video, storage_name},
 };
 use eframe::egui::{self, ProgressBar};
 use opencv::{
+    core::MatTraitConst,
     imgcodecs,
-    videoio::{self, VideoCaptureTrait},
+    videoio::{self, VideoCaptureTrait, VideoCaptureTraitConst},
 };
+use serde_json::json;
 
-use super::{home::HomeView, View};
+use super::{annotator_filter_ui, home::HomeView, View};
 
 pub struct ListView {
     progress: Option<Vec<Arc<Mutex<f32>>>>,
@@ -26,6 +29,9 @@ pub struct ListView {
 impl View for ListView {
     fn render(&mut self, ctx: &egui::Context, app: &mut GlobalState) -> Option<Box<dyn View>> {
         let mut next_view = None;
+        if let Some(project) = app.project.as_mut() {
+            project.load_all_annotations();
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             next_view = list_ui(ui, app, self);
             label_list(ui, app);
@@ -54,41 +60,60 @@ fn list_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut ListView) -> Op
             state.progress = Some(indicators);
             app.show_export_popup = true;
         }
+        ui.separator();
+        annotator_filter_ui(ui, app);
     });
     next_view
 }
 
 fn label_list(ui: &mut egui::Ui, app: &mut GlobalState) -> Option<Box<dyn View>> {
     let mut to_delete = Vec::new();
-    for (video, annotations) in app
-        .project
-        .as_mut()
-        .unwrap()
+    let project = app.project.as_ref().unwrap();
+    // frame rates come from the video library, which reads them in the background
+    let videos = project
+        .annotations
+        .keys()
+        .map(|video| (video.clone(), project.video_path(video)))
+        .collect::<Vec<_>>();
+    app.library.request(&videos, ui.ctx());
+    for (video, annotations) in project
         .annotations
         .iter()
         .filter(|(_, annotations)| !annotations.is_empty())
     {
+        let fps = app.library.info(video).map_or(0.0, |info| info.fps);
         ui.label(&format!("{}", video));
-        for (i, annotation) in annotations.iter().enumerate() {
+        for annotation in annotations
+            .iter()
+            .filter(|annotation| app.matches_annotator_filter(annotation))
+        {
             ui.horizontal(|ui| {
-                ui.label(&format!(
-                    "{}: {} - {}",
-                    annotation.label, annotation.start_frame, annotation.end_frame
-                ));
+                if let AnnotationKind::Event { tolerance } = annotation.kind {
+                    ui.label(&format!(
+                        "{}: @{} (±{})",
+                        annotation.label,
+                        format_frame(annotation.start_frame, fps),
+                        tolerance
+                    ));
+                } else {
+                    ui.label(&format!(
+                        "{}: {} - {}",
+                        annotation.label,
+                        format_frame(annotation.start_frame, fps),
+                        format_frame(annotation.end_frame, fps)
+                    ));
+                }
+                if let Some(author) = &annotation.author {
+                    ui.label(format!("by {}", author));
+                }
                 if ui.button("Delete").clicked() {
-                    to_delete.push((video.clone(), i));
+                    to_delete.push((video.clone(), annotation.id.clone()));
                 }
             });
         }
     }
-    for (video, i) in to_delete {
-        app.project
-            .as_mut()
-            .unwrap()
-            .annotations
-            .get_mut(&video)
-            .unwrap()
-            .remove(i);
+    for (video, id) in to_delete {
+        app.project.as_mut().unwrap().remove_annotation(&video, &id);
     }
     None
 }
@@ -126,15 +151,29 @@ fn export_labels(app: &GlobalState) -> Vec<Arc<Mutex<f32>>> {
         .unwrap()
         .annotations
         .iter()
-        .filter(|(_, annotations)| !annotations.is_empty())
+        .filter(|(_, annotations)| annotations.iter().any(|a| a.is_labeled()))
     {
         let indicator = Arc::new(Mutex::new(0.0));
         progress.push(indicator.clone());
-        let video_path = project.path.join(project.video_folder.join(video));
-        let export_dir = project.path.join(project.labels_folder.join(video));
-        let annotations = annotations.clone();
+        let video_path = project.video_path(video);
+        let export_dir = project
+            .path
+            .join(project.labels_folder.join(storage_name(video)));
+        // segments still waiting for a label have no class to export
+        let annotations = annotations
+            .iter()
+            .filter(|annotation| annotation.is_labeled())
+            .cloned()
+            .collect();
+        let label_attributes = project.label_attributes.clone();
         thread::spawn(move || {
-            export_labels_to_video(video_path, annotations, export_dir, indicator);
+            export_labels_to_video(
+                video_path,
+                annotations,
+                label_attributes,
+                export_dir,
+                indicator,
+            );
         });
     }
     progress
@@ -143,6 +182,7 @@ fn export_labels(app: &GlobalState) -> Vec<Arc<Mutex<f32>>> {
 fn export_labels_to_video(
     video_path: PathBuf,
     annotations: Vec<FrameAnnotation>,
+    label_attributes: HashMap<Label, Vec<AttributeDefinition>>,
     export_dir: PathBuf,
     progress: Arc<Mutex<f32>>,
 ) {
@@ -152,6 +192,10 @@ fn export_labels_to_video(
         .to_string_lossy()
         .to_string();
 
+    let (events, annotations): (Vec<_>, Vec<_>) = annotations
+        .into_iter()
+        .partition(|annotation| annotation.is_event());
+
     let mut label_indices = HashMap::new();
     let mut next_index = 0;
     let mut classes = Vec::new();
@@ -163,7 +207,20 @@ fn export_labels_to_video(
         }
     }
 
-    let mut csv_content = format!("filename,{}\n", classes.join(","));
+    let no_attributes = Vec::new();
+    let mut header = vec!["filename".to_string(), "annotation_ids".to_string()];
+    header.extend(classes.iter().map(|class| csv_field(class)));
+    for class in &classes {
+        header.push(csv_field(&format!("{}:confidence", class)));
+        header.push(csv_field(&format!("{}:note", class)));
+        for definition in label_attributes.get(class).unwrap_or(&no_attributes) {
+            header.push(csv_field(&format!("{}:{}", class, definition.name)));
+        }
+    }
+    // appended so that readers indexing the older columns by position keep working
+    header.push("frame".to_string());
+    header.push("timecode".to_string());
+    let mut csv_content = format!("{}\n", header.join(","));
 
     if let Err(e) = fs::create_dir_all(&export_dir) {
         eprintln!("Failed to create export directory: {:?}", e);
@@ -174,12 +231,25 @@ fn export_labels_to_video(
 
     println!("Exporting labels to {:?}", export_dir);
     let mut video = load_video(&video_path);
+    let fps = video.get(videoio::CAP_PROP_FPS).unwrap();
+    if !events.is_empty() {
+        export_events(&events, fps, &export_dir);
+    }
+    if !annotations.is_empty() {
+        export_segments(&annotations, fps, &export_dir);
+    }
     let end_frame = annotations
         .iter()
         .map(|annotation| annotation.end_frame)
         .max()
         .unwrap_or(0);
 
+    let has_boxes = annotations
+        .iter()
+        .any(|annotation| !annotation.keyframes.is_empty());
+    let mut coco_images = Vec::new();
+    let mut coco_annotations = Vec::new();
+
     let mut frame_classes = Vec::with_capacity(classes.len());
     for i in 0..end_frame {
         *progress.lock().unwrap() = i as f32 / end_frame as f32;
@@ -200,6 +270,11 @@ fn export_labels_to_video(
         let mut frame = opencv::core::Mat::default();
         video.set(videoio::CAP_PROP_POS_FRAMES, i as f64).unwrap();
         video.read(&mut frame).unwrap();
+        // prefer the frame's own timestamp, which also holds for variable frame rates
+        let seconds = match video.get(videoio::CAP_PROP_POS_MSEC) {
+            Ok(msec) if msec > 0.0 || i == 0 => msec / 1000.0,
+            _ => frame_seconds(i, fps),
+        };
 
         imgcodecs::imwrite(
             frame_path.to_str().unwrap(),
@@ -208,7 +283,56 @@ fn export_labels_to_video(
         )
         .unwrap();
 
-        csv_content.push_str(&format!("{}", frame_filename));
+        if has_boxes {
+            let size = frame.size().unwrap();
+            let (width, height) = (size.width as f32, size.height as f32);
+            let image_id = coco_images.len() + 1;
+            coco_images.push(json!({
+                "id": image_id,
+                "file_name": frame_filename,
+                "frame": i,
+                "timestamp": seconds,
+                "width": size.width,
+                "height": size.height,
+            }));
+
+            let mut yolo_content = String::new();
+            for annotation in &annotations {
+                let Some(bbox) = annotation.box_at(i) else {
+                    continue;
+                };
+                let class = label_indices[&annotation.label];
+                yolo_content.push_str(&format!(
+                    "{} {:.6} {:.6} {:.6} {:.6}\n",
+                    class,
+                    bbox.x + bbox.width / 2.0,
+                    bbox.y + bbox.height / 2.0,
+                    bbox.width,
+                    bbox.height
+                ));
+                coco_annotations.push(json!({
+                    "id": coco_annotations.len() + 1,
+                    "image_id": image_id,
+                    "category_id": class + 1,
+                    "bbox": [bbox.x * width, bbox.y * height, bbox.width * width, bbox.height * height],
+                    "area": bbox.width * width * bbox.height * height,
+                    "iscrowd": 0,
+                    "segment_id": annotation.id,
+                }));
+            }
+            let yolo_file = export_dir.join(format!("{}_frame_{:05}.txt", video_stem, i));
+            if let Err(e) = fs::write(&yolo_file, yolo_content) {
+                eprintln!("Failed to write YOLO file: {:?}", e);
+            }
+        }
+
+        let annotation_ids = annotations
+            .iter()
+            .filter(|annotation| annotation.contains(i))
+            .map(|annotation| annotation.id.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        csv_content.push_str(&format!("{},{}", frame_filename, annotation_ids));
         for class in &classes {
             if frame_classes.contains(&label_indices[class]) {
                 csv_content.push_str(&format!(",1"));
@@ -216,11 +340,97 @@ fn export_labels_to_video(
                 csv_content.push_str(&format!(",0"));
             }
         }
-        csv_content.push_str("\n");
+        for class in &classes {
+            let annotation = annotations
+                .iter()
+                .find(|annotation| &annotation.label == class && annotation.contains(i));
+            let confidence = annotation
+                .and_then(|annotation| annotation.confidence)
+                .map_or(String::new(), |confidence| confidence.to_string());
+            let note = annotation.map_or("", |annotation| annotation.note.as_str());
+            csv_content.push_str(&format!(",{},{}", confidence, csv_field(note)));
+            for definition in label_attributes.get(class).unwrap_or(&no_attributes) {
+                let value = annotation
+                    .and_then(|annotation| annotation.attributes.get(&definition.name))
+                    .map_or(String::new(), |value| value.to_string());
+                csv_content.push_str(&format!(",{}", csv_field(&value)));
+            }
+        }
+        csv_content.push_str(&format!(",{},{}\n", i, format_timecode(seconds)));
         frame_classes.clear();
     }
     if let Err(e) = fs::write(&csv_file, csv_content) {
         eprintln!("Failed to write CSV file: {:?}", e);
     }
+    if has_boxes {
+        let categories = classes
+            .iter()
+            .enumerate()
+            .map(|(i, class)| json!({ "id": i + 1, "name": class }))
+            .collect::<Vec<_>>();
+        let coco = json!({
+            "images": coco_images,
+            "annotations": coco_annotations,
+            "categories": categories,
+        });
+        if let Err(e) = fs::write(export_dir.join("classes.txt"), classes.join("\n")) {
+            eprintln!("Failed to write YOLO classes file: {:?}", e);
+        }
+        if let Err(e) = fs::write(export_dir.join("coco.json"), coco.to_string()) {
+            eprintln!("Failed to write COCO file: {:?}", e);
+        }
+    }
     *progress.lock().unwrap() = 1.0;
 }
+
+fn export_events(events: &[FrameAnnotation], fps: f64, export_dir: &Path) {
+    let mut csv_content = "id,frame,timestamp,timecode,label,tolerance\n".to_string();
+    let mut events = events.iter().collect::<Vec<_>>();
+    events.sort_by_key(|event| event.start_frame);
+    for event in events {
+        let tolerance = match event.kind {
+            AnnotationKind::Event { tolerance } => tolerance,
+            AnnotationKind::Interval => 0,
+        };
+        let timestamp = frame_seconds(event.start_frame, fps);
+        csv_content.push_str(&format!(
+            "{},{},{:.3},{},{},{}\n",
+            event.id,
+            event.start_frame,
+            timestamp,
+            format_timecode(timestamp),
+            csv_field(&event.label),
+            tolerance
+        ));
+    }
+    if let Err(e) = fs::write(export_dir.join("events.csv"), csv_content) {
+        eprintln!("Failed to write events file: {:?}", e);
+    }
+}
+
+/// Writes one row per interval with its frames and times, next to the per-frame labels.
+fn export_segments(annotations: &[FrameAnnotation], fps: f64, export_dir: &Path) {
+    let mut csv_content =
+        "id,label,start_frame,end_frame,start_time,end_time,start_timecode,end_timecode\n"
+            .to_string();
+    let mut annotations = annotations.iter().collect::<Vec<_>>();
+    annotations.sort_by_key(|annotation| (annotation.start_frame, annotation.end_frame));
+    for annotation in annotations {
+        let start = frame_seconds(annotation.start_frame, fps);
+        let end = frame_seconds(annotation.end_frame, fps);
+        csv_content.push_str(&format!(
+            "{},{},{},{},{:.3},{:.3},{},{}\n",
+            annotation.id,
+            csv_field(&annotation.label),
+            annotation.start_frame,
+            annotation.end_frame,
+            start,
+            end,
+            format_timecode(start),
+            format_timecode(end)
+        ));
+    }
+    if let Err(e) = fs::write(export_dir.join("segments.csv"), csv_content) {
+        eprintln!("Failed to write segments file: {:?}", e);
+    }
+}
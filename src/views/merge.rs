@@ -0,0 +1,201 @@
+use std::path::PathBuf;
+
+use rfd::FileDialog;
+
+use crate::{
+    app::{FrameAnnotation, GlobalState},
+    merge::{merge, resolve, MergeResult, Resolution},
+    project::{read_config, Project},
+    utils::format_frame,
+};
+
+use super::{home::HomeView, View};
+
+pub struct MergeView {
+    base_path: Option<PathBuf>,
+    theirs_path: Option<PathBuf>,
+    result: Option<MergeResult>,
+    resolutions: Vec<Option<Resolution>>,
+    error: Option<String>,
+}
+
+impl View for MergeView {
+    fn render(&mut self, ctx: &egui::Context, app: &mut GlobalState) -> Option<Box<dyn View>> {
+        let mut next_view = None;
+        if let Some(project) = app.project.as_mut() {
+            project.load_all_annotations();
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Back").clicked() {
+                    next_view = Some(Box::new(HomeView::new()) as Box<dyn View>);
+                }
+                ui.heading("Merge");
+            });
+            sources_ui(ui, app, self);
+            if let Some(error) = &self.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            if self.result.is_some() {
+                let next = conflicts_ui(ui, app, self);
+                if next.is_some() {
+                    next_view = next;
+                }
+            }
+        });
+        next_view
+    }
+}
+
+impl MergeView {
+    pub fn new() -> Self {
+        Self {
+            base_path: None,
+            theirs_path: None,
+            result: None,
+            resolutions: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+fn sources_ui(ui: &mut egui::Ui, app: &mut GlobalState, state: &mut MergeView) {
+    let project = app.project.as_mut().unwrap();
+    ui.label(format!("Ours: {}", project.path.display()));
+    for (name, path) in [
+        ("Base", &mut state.base_path),
+        ("Theirs", &mut state.theirs_path),
+    ] {
+        ui.horizontal(|ui| {
+            if ui.button(format!("{}...", name)).clicked() {
+                if let Some(picked) = FileDialog::new().pick_folder() {
+                    *path = Some(picked);
+                }
+            }
+            ui.label(
+                path.as_ref()
+                    .map_or("-".to_string(), |path| path.display().to_string()),
+            );
+        });
+    }
+
+    let (Some(base_path), Some(theirs_path)) = (&state.base_path, &state.theirs_path) else {
+        return;
+    };
+    if ui.button("Merge").clicked() {
+        let ours = match project.merge_config() {
+            Ok(ours) => ours,
+            Err(e) => {
+                state.error = Some(format!("Can't merge: {}", e));
+                return;
+            }
+        };
+        match (read_config(base_path), read_config(theirs_path)) {
+            (Ok(base), Ok(theirs)) => {
+                let result = merge(&base, &ours, &theirs);
+                state.resolutions = vec![None; result.conflicts.len()];
+                state.result = Some(result);
+                state.error = None;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                state.error = Some(format!("Failed to read project: {}", e));
+            }
+        }
+    }
+}
+
+fn describe(annotation: &Option<FrameAnnotation>, fps: f64) -> String {
+    annotation
+        .as_ref()
+        .map_or("deleted".to_string(), |annotation| {
+            format!(
+                "{}: {} - {}",
+                annotation.label,
+                format_frame(annotation.start_frame, fps),
+                format_frame(annotation.end_frame, fps)
+            )
+        })
+}
+
+fn conflicts_ui(
+    ui: &mut egui::Ui,
+    app: &mut GlobalState,
+    state: &mut MergeView,
+) -> Option<Box<dyn View>> {
+    let mut next_view = None;
+    let result = state.result.as_ref().unwrap();
+
+    ui.separator();
+    if !result.setting_conflicts.is_empty() {
+        ui.colored_label(
+            egui::Color32::YELLOW,
+            "Both sides changed these settings, ours are kept:",
+        );
+        for conflict in &result.setting_conflicts {
+            ui.label(conflict.describe());
+        }
+    }
+    ui.label(format!("{} conflicts", result.conflicts.len()));
+    // frame rates come from the video library, which reads them in the background
+    let project = app.project.as_ref().unwrap();
+    let videos = result
+        .conflicts
+        .iter()
+        .map(|conflict| (conflict.video.clone(), project.video_path(&conflict.video)))
+        .collect::<Vec<_>>();
+    app.library.request(&videos, ui.ctx());
+    let library = &app.library;
+    egui::ScrollArea::vertical()
+        .max_height(400.0)
+        .show(ui, |ui| {
+            egui::Grid::new("merge_conflicts")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Video");
+                    ui.label("Base");
+                    ui.label("Ours");
+                    ui.label("Theirs");
+                    ui.label("Keep");
+                    ui.end_row();
+                    for (conflict, resolution) in
+                        result.conflicts.iter().zip(state.resolutions.iter_mut())
+                    {
+                        let fps = library.info(&conflict.video).map_or(0.0, |info| info.fps);
+                        ui.label(&conflict.video);
+                        ui.label(describe(&conflict.base, fps));
+                        ui.label(describe(&conflict.ours, fps));
+                        ui.label(describe(&conflict.theirs, fps));
+                        ui.horizontal(|ui| {
+                            ui.radio_value(resolution, Some(Resolution::Ours), "ours");
+                            ui.radio_value(resolution, Some(Resolution::Theirs), "theirs");
+                            ui.radio_value(resolution, Some(Resolution::Both), "both");
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+
+    let resolved = state
+        .resolutions
+        .iter()
+        .all(|resolution| resolution.is_some());
+    if ui
+        .add_enabled(resolved, egui::Button::new("Apply"))
+        .clicked()
+    {
+        let result = state.result.take().unwrap();
+        let mut config = result.config;
+        for (conflict, resolution) in result.conflicts.iter().zip(state.resolutions.iter()) {
+            resolve(&mut config, conflict, resolution.unwrap());
+        }
+        let project = app.project.as_mut().unwrap();
+        let mut merged = Project::from(config, project.path.clone());
+        merged.locked_by = project.locked_by.take();
+        merged.lock_error = project.lock_error.take();
+        merged.loaded_modified = project.loaded_modified;
+        merged.path_variables = std::mem::take(&mut project.path_variables);
+        *project = merged;
+        next_view = Some(Box::new(HomeView::new()) as Box<dyn View>);
+    }
+    next_view
+}
@@ -1,9 +1,49 @@
-use crate::app::GlobalState;
+use crate::{app::GlobalState, project::VideoStatus};
 
+pub mod agreement;
+pub mod evaluation;
 pub mod home;
 pub mod label;
 pub mod list;
+pub mod merge;
+pub mod queue;
 
 pub trait View {
     fn render(&mut self, ctx: &egui::Context, app: &mut GlobalState) -> Option<Box<dyn View>>;
 }
+
+pub fn annotator_filter_ui(ui: &mut egui::Ui, app: &mut GlobalState) {
+    let annotators = app.annotators();
+    egui::ComboBox::from_id_salt("annotator_filter")
+        .selected_text(
+            app.annotator_filter
+                .clone()
+                .unwrap_or("All annotators".to_string()),
+        )
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut app.annotator_filter, None, "All annotators");
+            for annotator in annotators {
+                ui.selectable_value(
+                    &mut app.annotator_filter,
+                    Some(annotator.clone()),
+                    annotator,
+                );
+            }
+        });
+}
+
+pub fn status_badge(ui: &mut egui::Ui, status: &VideoStatus) -> egui::Response {
+    let color = match status {
+        VideoStatus::Unlabeled => egui::Color32::GRAY,
+        VideoStatus::InProgress => egui::Color32::LIGHT_BLUE,
+        VideoStatus::Submitted => egui::Color32::GOLD,
+        VideoStatus::Approved => egui::Color32::GREEN,
+        VideoStatus::Rejected { .. } => egui::Color32::RED,
+    };
+    let badge = ui.label(egui::RichText::new(status.name()).color(color).strong());
+    if let VideoStatus::Rejected { comment } = status {
+        badge.on_hover_text(comment)
+    } else {
+        badge
+    }
+}
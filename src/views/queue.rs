@@ -0,0 +1,134 @@
+use crate::{
+    app::GlobalState,
+    uncertainty::{ReviewQueue, UncertaintyMeasure},
+};
+
+use super::{home::HomeView, label::LabelView, View};
+
+/// Number of regions listed, the queue itself can be much longer.
+const LISTED_REGIONS: usize = 200;
+
+pub struct QueueView {
+    measure: UncertaintyMeasure,
+    region_frames: u32,
+}
+
+impl View for QueueView {
+    fn render(&mut self, ctx: &egui::Context, app: &mut GlobalState) -> Option<Box<dyn View>> {
+        let mut next_view = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Back").clicked() {
+                    next_view = Some(Box::new(HomeView::new()) as Box<dyn View>);
+                }
+                ui.heading("Review queue");
+            });
+            ui.label("Unlabeled regions of videos with imported or computed scores, where the model is least sure first.");
+            ui.horizontal(|ui| {
+                ui.label("Uncertainty");
+                egui::ComboBox::from_id_salt("uncertainty_measure")
+                    .selected_text(self.measure.name())
+                    .show_ui(ui, |ui| {
+                        for measure in UncertaintyMeasure::ALL {
+                            ui.selectable_value(&mut self.measure, measure, measure.name());
+                        }
+                    });
+                ui.label("Region length");
+                ui.add(
+                    egui::DragValue::new(&mut self.region_frames)
+                        .range(1..=10000)
+                        .suffix(" frames"),
+                );
+                if ui.button("Build queue").clicked() {
+                    let project = app.project.as_mut().unwrap();
+                    app.review_queue =
+                        Some(ReviewQueue::build(project, self.measure, self.region_frames));
+                }
+                if app.review_queue.is_some() && ui.button("Clear").clicked() {
+                    app.review_queue = None;
+                }
+            });
+            let next = regions_ui(ui, app);
+            if next.is_some() {
+                next_view = next;
+            }
+        });
+        next_view
+    }
+}
+
+impl QueueView {
+    pub fn new(app: &GlobalState) -> Self {
+        Self {
+            measure: app
+                .review_queue
+                .as_ref()
+                .map_or(UncertaintyMeasure::Entropy, |queue| queue.measure),
+            region_frames: 50,
+        }
+    }
+}
+
+fn regions_ui(ui: &mut egui::Ui, app: &mut GlobalState) -> Option<Box<dyn View>> {
+    let queue = app.review_queue.as_ref()?;
+    let mut opened = None;
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "{} regions, at {}",
+            queue.regions.len(),
+            queue.position + 1
+        ));
+        if queue.current().is_some() && ui.button("Continue").clicked() {
+            opened = Some(queue.position);
+        }
+    });
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("review_queue")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("#");
+                ui.label("Video");
+                ui.label("Frames");
+                ui.label("Uncertainty");
+                ui.label("Model guess");
+                ui.label("");
+                ui.end_row();
+                for (i, region) in queue
+                    .regions
+                    .iter()
+                    .enumerate()
+                    .skip(queue.position)
+                    .take(LISTED_REGIONS)
+                {
+                    ui.label((i + 1).to_string());
+                    ui.label(&region.video);
+                    ui.label(format!("{} - {}", region.start_frame, region.end_frame));
+                    ui.add(
+                        egui::ProgressBar::new(region.uncertainty)
+                            .desired_width(100.0)
+                            .text(format!("{:.2}", region.uncertainty)),
+                    );
+                    ui.label(&region.guess);
+                    if ui.button("Open").clicked() {
+                        opened = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+    let i = opened?;
+    app.review_queue.as_mut().unwrap().position = i;
+    open_current(app)
+}
+
+/// Opens the current region of the review queue in the labeling view.
+pub fn open_current(app: &mut GlobalState) -> Option<Box<dyn View>> {
+    let region = app.review_queue.as_ref()?.current()?;
+    let project = app.project.as_ref().unwrap();
+    let path = project.video_path(&region.video);
+    let mut view = LabelView::open(project, region.video.clone(), path);
+    view.focus(region.start_frame, region.end_frame);
+    app.annotator_filter = None;
+    Some(Box::new(view))
+}